@@ -0,0 +1,102 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//!
+//! Codec throughput over a long stream of prompt-terminated text lines,
+//! compared with a baseline that handles one byte per frame.
+//!
+//! Run with `cargo bench --bench codec`.
+//!
+
+use bytes::{Bytes, BytesMut};
+use std::time::Instant;
+use tokio_telnet::codec::{TelnetCodec, TelnetFrame};
+use tokio_util::codec::{Decoder, Encoder};
+
+const LINE: &[u8] =
+    b"You are standing in an open field west of a white house.\r\n";
+const GA: [u8; 2] = [255, 249];
+const SIZE: usize = 16 * 1024 * 1024;
+
+fn input() -> Vec<u8> {
+    let mut data = Vec::with_capacity(SIZE + LINE.len() + GA.len());
+    while data.len() < SIZE {
+        data.extend_from_slice(LINE);
+        data.extend_from_slice(&GA);
+    }
+    data
+}
+
+fn mib_per_second(bytes: usize, start: Instant) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0) / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let data = input();
+    let mut codec = TelnetCodec::default();
+
+    let mut src = BytesMut::from(&data[..]);
+    let mut frames = 0usize;
+    let start = Instant::now();
+    while codec.decode(&mut src).unwrap().is_some() {
+        frames += 1;
+    }
+    println!(
+        "decode: {:.1} MiB/s ({} frames)",
+        mib_per_second(data.len(), start),
+        frames
+    );
+
+    // Baseline: one byte per frame, as the per-byte path produced.
+    let mut src = BytesMut::new();
+    let mut frames = 0usize;
+    let start = Instant::now();
+    for byte in data.iter() {
+        src.extend_from_slice(&[*byte]);
+        while codec.decode(&mut src).unwrap().is_some() {
+            frames += 1;
+        }
+    }
+    println!(
+        "decode per byte: {:.1} MiB/s ({} frames)",
+        mib_per_second(data.len(), start),
+        frames
+    );
+
+    let lines = data.len() / (LINE.len() + GA.len());
+    let mut dst = BytesMut::new();
+    let start = Instant::now();
+    for _ in 0..lines {
+        let line = Bytes::from_static(LINE);
+        codec.encode(TelnetFrame::Data(line), &mut dst).unwrap();
+        codec.encode(TelnetFrame::GoAhead, &mut dst).unwrap();
+    }
+    println!("encode: {:.1} MiB/s", mib_per_second(dst.len(), start));
+
+    let mut dst = BytesMut::new();
+    let start = Instant::now();
+    for _ in 0..lines {
+        for byte in LINE.iter() {
+            let data = Bytes::copy_from_slice(&[*byte]);
+            codec.encode(TelnetFrame::Data(data), &mut dst).unwrap();
+        }
+        codec.encode(TelnetFrame::GoAhead, &mut dst).unwrap();
+    }
+    println!(
+        "encode per byte: {:.1} MiB/s",
+        mib_per_second(dst.len(), start)
+    );
+}
@@ -15,6 +15,6 @@
 //
 
 ///
-///
+/// Telnet Client
 ///
 pub struct TelnetClient {}
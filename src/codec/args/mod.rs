@@ -14,16 +14,21 @@
 // limitations under the License.
 //
 
-use bytes::BufMut;
+use bytes::{Buf, BufMut};
 
 ///
 /// Telnet Subnegotiation Argument
 ///
+#[derive(Clone, Debug, PartialEq)]
 pub enum TelnetArgument {
     Unknown(Vec<u8>),
 }
 
-pub trait Argument {
+///
+/// Typed Subnegotiation Argument
+///
+#[allow(clippy::len_without_is_empty)]
+pub trait Argument: Sized {
     ///
     /// Get Encoded Length of `Argument`
     ///
@@ -33,7 +38,23 @@ pub trait Argument {
     ///
     fn encode<T: BufMut>(&self, dst: &mut T);
     ///
-    /// Decode `Argument` to `BufMut`
+    /// Decode `Argument` from `Buf`, returning `None` if it is malformed.
     ///
-    fn dencode<T: BufMut>(&self, src: &mut T) -> Self;
+    fn decode<T: Buf>(src: &mut T) -> Option<Self>;
+}
+
+impl Argument for TelnetArgument {
+    fn len(&self) -> usize {
+        match self {
+            TelnetArgument::Unknown(arguments) => arguments.len(),
+        }
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        match self {
+            TelnetArgument::Unknown(arguments) => dst.put_slice(arguments),
+        }
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        Some(TelnetArgument::Unknown(src.to_bytes().to_vec()))
+    }
 }
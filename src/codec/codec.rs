@@ -14,21 +14,62 @@
 // limitations under the License.
 //
 
+use super::config::{CodecConfiguration, DecoderMode, ViolationPolicy};
 use super::error::DecodeError;
 use super::error::EncodeError;
-use super::frame::TelnetFrame;
+use super::frame::{ProtocolViolation, TelnetFrame};
+use super::options::TelnetOption;
 use crate::consts;
 
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 use tracing::{event, Level};
 
 ///
-///
+/// Telnet Codec
 ///
 pub struct TelnetCodec {
+    config: CodecConfiguration,
     decoder_buffer: BytesMut,
     decoder_state: DecoderState,
+    /// Current subnegotiation exceeded the configured length
+    decoder_overflow: bool,
+    /// Interrupted subnegotiation reported once its discarded tail ends
+    decoder_violation: Option<ProtocolViolation>,
+    /// Frames decoded since the decoder last ran out of input
+    pending_frames: usize,
+    /// Keep received data out of tracing events
+    redacted: bool,
+}
+
+impl TelnetCodec {
+    ///
+    /// Create a new `TelnetCodec` enforcing the limits in `config`.
+    ///
+    pub fn with_configuration(config: CodecConfiguration) -> TelnetCodec {
+        TelnetCodec {
+            config,
+            decoder_buffer: BytesMut::new(),
+            decoder_state: DecoderState::NormalData,
+            decoder_overflow: false,
+            decoder_violation: None,
+            pending_frames: 0,
+            redacted: false,
+        }
+    }
+    ///
+    /// Keep received data out of tracing events, for example while a
+    /// password is being read.
+    ///
+    pub fn set_redacted(&mut self, redacted: bool) {
+        self.redacted = redacted;
+    }
+    ///
+    /// Limits this codec enforces.
+    ///
+    pub fn configuration(&self) -> &CodecConfiguration {
+        &self.config
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -51,6 +92,10 @@ enum DecoderState {
     SubnegotiateArgument(u8),
     /// Received IAC during Subnegotiation, Next Byte is command
     SubnegotiateArgumentIAC(u8),
+    /// Discarding the rest of an interrupted Subnegotiation
+    SubnegotiateDiscard,
+    /// Received IAC while discarding, Next Byte may end the Subnegotiation
+    SubnegotiateDiscardIAC,
 }
 
 impl Decoder for TelnetCodec {
@@ -64,15 +109,106 @@ impl Decoder for TelnetCodec {
         &mut self,
         src: &mut BytesMut,
     ) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let frame = match self.decode_frame(src)? {
+                Some(frame) => frame,
+                None => {
+                    self.pending_frames = 0;
+                    return Ok(None);
+                }
+            };
+            let limit = self.config.max_pending_frames();
+            self.pending_frames = self.pending_frames.saturating_add(1);
+            if self.pending_frames <= limit {
+                return Ok(Some(frame));
+            }
+            if self.pending_frames == limit + 1 {
+                event!(Level::WARN, "More than {} frames pending", limit);
+            }
+            match self.config.pending_frames_policy() {
+                ViolationPolicy::Drop => continue,
+                ViolationPolicy::Truncate => {
+                    src.clear();
+                    self.decoder_state = DecoderState::NormalData;
+                    self.decoder_buffer.clear();
+                    self.decoder_overflow = false;
+                    self.decoder_violation = None;
+                    self.pending_frames = 0;
+                    return Ok(None);
+                }
+                ViolationPolicy::Error => {
+                    self.pending_frames = 0;
+                    return Err(DecodeError::TooManyPendingFrames(limit));
+                }
+            }
+        }
+    }
+}
+
+impl TelnetCodec {
+    ///
+    /// Decode the next frame from `src`, ignoring the pending frame limit.
+    ///
+    fn decode_frame(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<TelnetFrame>, DecodeError> {
         while src.remaining() > 0 {
+            if let DecoderState::NormalData = self.decoder_state {
+                // Return every byte up to the next IAC as a single frame.
+                let run = src
+                    .iter()
+                    .position(|&b| b == consts::IAC)
+                    .unwrap_or_else(|| src.len());
+                if run > 0 {
+                    let data = src.split_to(run).freeze();
+                    return Ok(Some(TelnetFrame::Data(data)));
+                }
+            }
+            if let DecoderState::SubnegotiateDiscard = self.decoder_state {
+                let run = src
+                    .iter()
+                    .position(|&b| b == consts::IAC)
+                    .unwrap_or_else(|| src.len());
+                self.discard(run);
+                src.advance(run);
+                if src.is_empty() {
+                    continue;
+                }
+            }
+            if let DecoderState::SubnegotiateArgument(option) =
+                self.decoder_state
+            {
+                let run = src
+                    .iter()
+                    .position(|&b| b == consts::IAC)
+                    .unwrap_or_else(|| src.len());
+                if self.decoder_buffer.is_empty()
+                    && !self.decoder_overflow
+                    && run <= self.config.max_subnegotiation()
+                    && src.get(run + 1) == Some(&consts::SE)
+                {
+                    // Payload is complete and unescaped, hand it out without
+                    // copying.
+                    let arguments = src.split_to(run).freeze();
+                    src.advance(2);
+                    self.decoder_state = DecoderState::NormalData;
+                    return Ok(Some(TelnetFrame::Subnegotiate(
+                        TelnetOption::from(option),
+                        arguments,
+                    )));
+                }
+                self.push_arguments(&src[..run])?;
+                src.advance(run);
+                if src.is_empty() {
+                    continue;
+                }
+            }
             let byte = src.get_u8();
             match (self.decoder_state, byte) {
-                (DecoderState::NormalData, consts::IAC) => {
-                    self.decoder_state = DecoderState::InterpretAsCommand;
-                }
                 (DecoderState::NormalData, _) => {
-                    self.decoder_state = DecoderState::NormalData;
-                    return Ok(Some(TelnetFrame::Data(byte)));
+                    // Only reached for IAC, other bytes are consumed above.
+                    self.decoder_state = DecoderState::InterpretAsCommand;
                 }
                 (DecoderState::InterpretAsCommand, consts::NOP) => {
                     self.decoder_state = DecoderState::NormalData;
@@ -110,9 +246,27 @@ impl Decoder for TelnetCodec {
                     self.decoder_state = DecoderState::NormalData;
                     return Ok(Some(TelnetFrame::GoAhead));
                 }
+                (DecoderState::InterpretAsCommand, consts::EOF) => {
+                    self.decoder_state = DecoderState::NormalData;
+                    return Ok(Some(TelnetFrame::EndOfFile));
+                }
+                (DecoderState::InterpretAsCommand, consts::SUSP) => {
+                    self.decoder_state = DecoderState::NormalData;
+                    return Ok(Some(TelnetFrame::Suspend));
+                }
+                (DecoderState::InterpretAsCommand, consts::ABORT) => {
+                    self.decoder_state = DecoderState::NormalData;
+                    return Ok(Some(TelnetFrame::Abort));
+                }
+                (DecoderState::InterpretAsCommand, consts::EOR) => {
+                    self.decoder_state = DecoderState::NormalData;
+                    return Ok(Some(TelnetFrame::EndOfRecord));
+                }
                 (DecoderState::InterpretAsCommand, consts::IAC) => {
                     self.decoder_state = DecoderState::NormalData;
-                    return Ok(Some(TelnetFrame::Data(consts::IAC)));
+                    return Ok(Some(TelnetFrame::Data(Bytes::from_static(&[
+                        consts::IAC,
+                    ]))));
                 }
                 (DecoderState::InterpretAsCommand, consts::DO) => {
                     self.decoder_state = DecoderState::NegotiateDo;
@@ -130,26 +284,47 @@ impl Decoder for TelnetCodec {
                     self.decoder_state = DecoderState::Subnegotiate;
                 }
                 (DecoderState::InterpretAsCommand, _) => {
-                    // Return to NormalData State, and return a No Operation
-                    event!(Level::WARN, "Received Unknown Command {:#X}", byte);
+                    // Return to NormalData State, dropping the command
+                    if self.redacted {
+                        event!(
+                            Level::WARN,
+                            "Received Unknown Command <redacted>"
+                        );
+                    } else {
+                        event!(
+                            Level::WARN,
+                            "Received Unknown Command {:#X}",
+                            byte
+                        );
+                    }
                     self.decoder_state = DecoderState::NormalData;
-                    return Ok(Some(TelnetFrame::NoOperation));
+                    if let ViolationPolicy::Error =
+                        self.config.unknown_command_policy()
+                    {
+                        return Err(DecodeError::UnknownCommand(byte));
+                    }
                 }
                 (DecoderState::NegotiateDo, _) => {
                     self.decoder_state = DecoderState::NormalData;
-                    return Ok(Some(TelnetFrame::Do(byte)));
+                    return Ok(Some(TelnetFrame::Do(TelnetOption::from(byte))));
                 }
                 (DecoderState::NegotiateDont, _) => {
                     self.decoder_state = DecoderState::NormalData;
-                    return Ok(Some(TelnetFrame::Dont(byte)));
+                    return Ok(Some(TelnetFrame::Dont(TelnetOption::from(
+                        byte,
+                    ))));
                 }
                 (DecoderState::NegotiateWill, _) => {
                     self.decoder_state = DecoderState::NormalData;
-                    return Ok(Some(TelnetFrame::Will(byte)));
+                    return Ok(Some(TelnetFrame::Will(TelnetOption::from(
+                        byte,
+                    ))));
                 }
                 (DecoderState::NegotiateWont, _) => {
                     self.decoder_state = DecoderState::NormalData;
-                    return Ok(Some(TelnetFrame::Wont(byte)));
+                    return Ok(Some(TelnetFrame::Wont(TelnetOption::from(
+                        byte,
+                    ))));
                 }
                 (DecoderState::Subnegotiate, _) => {
                     self.decoder_state =
@@ -160,30 +335,141 @@ impl Decoder for TelnetCodec {
                         DecoderState::SubnegotiateArgumentIAC(option);
                 }
                 (DecoderState::SubnegotiateArgument(_option), _) => {
-                    self.decoder_buffer.put_u8(byte);
+                    // Runs without IAC are normally consumed above.
+                    self.push_arguments(&[byte])?;
                 }
-                (DecoderState::SubnegotiateArgumentIAC(option), consts::IAC) => {
+                (
+                    DecoderState::SubnegotiateArgumentIAC(option),
+                    consts::IAC,
+                ) => {
                     self.decoder_state =
                         DecoderState::SubnegotiateArgument(option);
-                    self.decoder_buffer.put_u8(consts::IAC);
+                    self.push_arguments(&[consts::IAC])?;
                 }
                 (DecoderState::SubnegotiateArgumentIAC(option), consts::SE) => {
                     self.decoder_state = DecoderState::NormalData;
-                    let buffer = Vec::from(self.decoder_buffer.as_ref());
-                    self.decoder_buffer.clear();
-                    return Ok(Some(TelnetFrame::Subnegotiate(option, buffer)));
+                    let arguments = self.decoder_buffer.split().freeze();
+                    let overflow =
+                        std::mem::replace(&mut self.decoder_overflow, false);
+                    if overflow
+                        && self.config.subnegotiation_policy()
+                            != ViolationPolicy::Truncate
+                    {
+                        continue;
+                    }
+                    return Ok(Some(TelnetFrame::Subnegotiate(
+                        TelnetOption::from(option),
+                        arguments,
+                    )));
                 }
-                (DecoderState::SubnegotiateArgumentIAC(_), _) => {
-                    // TODO: Evaluate if better to return back to SubnegotiateArgumentIAC state and keep buffer
+                (DecoderState::SubnegotiateArgumentIAC(option), _) => {
+                    if let DecoderMode::Lenient = self.config.mode() {
+                        // Broken peers often forget to escape IAC.
+                        self.decoder_state =
+                            DecoderState::SubnegotiateArgument(option);
+                        self.push_arguments(&[consts::IAC, byte])?;
+                        continue;
+                    }
+                    if self.redacted {
+                        event!(
+                            Level::WARN,
+                            "Received invalid Command <redacted> during \
+                             Subnegotiation"
+                        );
+                    } else {
+                        event!(
+                            Level::WARN,
+                            "Received invalid Command {:#X} during \
+                             Subnegotiation",
+                            byte
+                        );
+                    }
+                    // The rest of the payload is not terminal data, drop it
+                    // up to the IAC SE that ends it.
+                    self.decoder_state = DecoderState::SubnegotiateDiscard;
+                    self.decoder_overflow = false;
+                    let arguments = self.decoder_buffer.split().freeze();
+                    if let DecoderMode::Strict = self.config.mode() {
+                        return Err(DecodeError::InvalidSubnegotiation(byte));
+                    }
+                    // Reported once the discarded tail is known.
+                    self.decoder_violation =
+                        Some(ProtocolViolation::InterruptedSubnegotiation {
+                            option: TelnetOption::from(option),
+                            command: byte,
+                            arguments,
+                            discarded: 0,
+                        });
+                }
+                (DecoderState::SubnegotiateDiscard, _) => {
+                    // Only reached for IAC, other bytes are skipped above.
+                    self.decoder_state = DecoderState::SubnegotiateDiscardIAC;
+                }
+                (DecoderState::SubnegotiateDiscardIAC, consts::SE) => {
                     self.decoder_state = DecoderState::NormalData;
-                    self.decoder_buffer.clear();
-                    event!(Level::WARN, "Received Unknown or invalid Command during Subnegotiation {:#X}. Aborting", byte);
-                    return Ok(Some(TelnetFrame::NoOperation));
+                    if let Some(violation) = self.decoder_violation.take() {
+                        return Ok(Some(TelnetFrame::ProtocolViolation(
+                            violation,
+                        )));
+                    }
+                }
+                (DecoderState::SubnegotiateDiscardIAC, consts::IAC) => {
+                    self.decoder_state = DecoderState::SubnegotiateDiscard;
+                    self.discard(1);
+                }
+                (DecoderState::SubnegotiateDiscardIAC, _) => {
+                    self.decoder_state = DecoderState::SubnegotiateDiscard;
+                    self.discard(2);
                 }
             }
         }
         Ok(None)
     }
+    ///
+    /// Count `count` bytes towards the interrupted subnegotiation being
+    /// reported, if any.
+    ///
+    fn discard(&mut self, count: usize) {
+        if let Some(ProtocolViolation::InterruptedSubnegotiation {
+            discarded,
+            ..
+        }) = self.decoder_violation.as_mut()
+        {
+            *discarded += count;
+        }
+    }
+    ///
+    /// Append `arguments` to the subnegotiation being assembled, enforcing
+    /// the configured length limit.
+    ///
+    fn push_arguments(&mut self, arguments: &[u8]) -> Result<(), DecodeError> {
+        if self.decoder_overflow {
+            return Ok(());
+        }
+        let limit = self.config.max_subnegotiation();
+        let room = limit.saturating_sub(self.decoder_buffer.len());
+        if arguments.len() <= room {
+            self.decoder_buffer.extend_from_slice(arguments);
+            return Ok(());
+        }
+        // Everything up to IAC SE is discarded from here on.
+        event!(Level::WARN, "Subnegotiation exceeded {} bytes", limit);
+        self.decoder_overflow = true;
+        match self.config.subnegotiation_policy() {
+            ViolationPolicy::Drop => {
+                self.decoder_buffer.clear();
+                Ok(())
+            }
+            ViolationPolicy::Truncate => {
+                self.decoder_buffer.extend_from_slice(&arguments[..room]);
+                Ok(())
+            }
+            ViolationPolicy::Error => {
+                self.decoder_buffer.clear();
+                Err(DecodeError::SubnegotiationTooLong(limit))
+            }
+        }
+    }
 }
 
 impl Encoder for TelnetCodec {
@@ -196,12 +482,14 @@ impl Encoder for TelnetCodec {
         dst: &mut BytesMut,
     ) -> Result<(), Self::Error> {
         match item {
-            TelnetFrame::Data(ch) => {
-                dst.reserve(2);
-                if ch == consts::IAC {
-                    dst.put_u8(consts::IAC);
-                }
-                dst.put_u8(ch);
+            TelnetFrame::Data(data) => {
+                put_escaped(&data, dst);
+            }
+            TelnetFrame::ProtocolViolation(_) if self.redacted => {
+                event!(Level::DEBUG, "Not encoding <redacted>");
+            }
+            TelnetFrame::ProtocolViolation(violation) => {
+                event!(Level::DEBUG, "Not encoding {:?}", violation);
             }
             TelnetFrame::NoOperation => {
                 dst.reserve(2);
@@ -248,36 +536,58 @@ impl Encoder for TelnetCodec {
                 dst.put_u8(consts::IAC);
                 dst.put_u8(consts::GA);
             }
+            TelnetFrame::EndOfFile => {
+                dst.reserve(2);
+                dst.put_u8(consts::IAC);
+                dst.put_u8(consts::EOF);
+            }
+            TelnetFrame::Suspend => {
+                dst.reserve(2);
+                dst.put_u8(consts::IAC);
+                dst.put_u8(consts::SUSP);
+            }
+            TelnetFrame::Abort => {
+                dst.reserve(2);
+                dst.put_u8(consts::IAC);
+                dst.put_u8(consts::ABORT);
+            }
+            TelnetFrame::EndOfRecord => {
+                dst.reserve(2);
+                dst.put_u8(consts::IAC);
+                dst.put_u8(consts::EOR);
+            }
             TelnetFrame::Do(option) => {
                 dst.reserve(3);
                 dst.put_u8(consts::IAC);
                 dst.put_u8(consts::DO);
-                dst.put_u8(option);
+                dst.put_u8(u8::from(option));
             }
             TelnetFrame::Dont(option) => {
                 dst.reserve(3);
                 dst.put_u8(consts::IAC);
                 dst.put_u8(consts::DONT);
-                dst.put_u8(option);
+                dst.put_u8(u8::from(option));
             }
             TelnetFrame::Will(option) => {
                 dst.reserve(3);
                 dst.put_u8(consts::IAC);
                 dst.put_u8(consts::WILL);
-                dst.put_u8(option);
+                dst.put_u8(u8::from(option));
             }
             TelnetFrame::Wont(option) => {
                 dst.reserve(3);
                 dst.put_u8(consts::IAC);
                 dst.put_u8(consts::WONT);
-                dst.put_u8(option);
+                dst.put_u8(u8::from(option));
             }
             TelnetFrame::Subnegotiate(option, arguments) => {
+                // IAC within the arguments must be doubled, mirroring the
+                // SubnegotiateArgumentIAC decoder state.
                 dst.reserve(5 + arguments.len());
                 dst.put_u8(consts::IAC);
                 dst.put_u8(consts::SB);
-                dst.put_u8(option);
-                dst.put_slice(&arguments);
+                dst.put_u8(u8::from(option));
+                put_escaped(&arguments, dst);
                 dst.put_u8(consts::IAC);
                 dst.put_u8(consts::SE);
             }
@@ -286,11 +596,26 @@ impl Encoder for TelnetCodec {
     }
 }
 
+///
+/// Write `data` to `dst`, doubling every IAC.
+///
+fn put_escaped(data: &[u8], dst: &mut BytesMut) {
+    let escapes = data.iter().filter(|&&b| b == consts::IAC).count();
+    dst.reserve(data.len() + escapes);
+    if escapes == 0 {
+        dst.put_slice(data);
+        return;
+    }
+    for chunk in data.split_inclusive(|&b| b == consts::IAC) {
+        dst.put_slice(chunk);
+        if chunk.last() == Some(&consts::IAC) {
+            dst.put_u8(consts::IAC);
+        }
+    }
+}
+
 impl Default for TelnetCodec {
     fn default() -> TelnetCodec {
-        TelnetCodec {
-            decoder_buffer: BytesMut::new(),
-            decoder_state: DecoderState::NormalData,
-        }
+        TelnetCodec::with_configuration(CodecConfiguration::default())
     }
 }
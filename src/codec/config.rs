@@ -0,0 +1,219 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+///
+/// What the decoder does when the remote breaks one of the limits in a
+/// [`CodecConfiguration`].
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ViolationPolicy {
+    /// Discard the offending input and carry on decoding.
+    Drop,
+    /// Keep as much of the offending input as the limit allows.
+    Truncate,
+    /// Fail decoding with a `DecodeError`.
+    Error,
+}
+
+///
+/// How the decoder resynchronises when a subnegotiation is interrupted by
+/// IAC followed by anything other than IAC or SE.
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DecoderMode {
+    /// Fail decoding with `DecodeError::InvalidSubnegotiation`. The rest of
+    /// the subnegotiation, up to IAC SE, is discarded.
+    Strict,
+    /// Keep the subnegotiation going, treating the IAC and the byte after it
+    /// as payload.
+    Lenient,
+    /// Discard the subnegotiation up to IAC SE, then emit a
+    /// `TelnetFrame::ProtocolViolation` with the payload before the invalid
+    /// command and the number of bytes discarded after it.
+    Report,
+}
+
+///
+/// Limits enforced by [`TelnetCodec`](super::TelnetCodec) on data received
+/// from the remote.
+///
+/// Use [`CodecConfiguration::builder`] to change the defaults.
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CodecConfiguration {
+    /// Longest subnegotiation payload accepted
+    max_subnegotiation: usize,
+    /// Policy when a subnegotiation payload is too long
+    subnegotiation_policy: ViolationPolicy,
+    /// Most frames decoded from buffered input without waiting for more
+    max_pending_frames: usize,
+    /// Policy when too many frames are pending
+    pending_frames_policy: ViolationPolicy,
+    /// Policy when an unknown command follows IAC
+    unknown_command_policy: ViolationPolicy,
+    /// Behaviour when a subnegotiation is interrupted
+    mode: DecoderMode,
+}
+
+impl CodecConfiguration {
+    ///
+    /// Create a new `CodecConfigurationBuilder`
+    ///
+    pub fn builder() -> CodecConfigurationBuilder {
+        CodecConfigurationBuilder::default()
+    }
+    ///
+    /// Longest subnegotiation payload accepted, after IAC unescaping.
+    ///
+    pub fn max_subnegotiation(&self) -> usize {
+        self.max_subnegotiation
+    }
+    ///
+    /// Policy applied to subnegotiations longer than `max_subnegotiation`.
+    ///
+    /// `Drop` discards the whole subnegotiation, `Truncate` delivers the
+    /// first `max_subnegotiation` bytes of it.
+    ///
+    pub fn subnegotiation_policy(&self) -> ViolationPolicy {
+        self.subnegotiation_policy
+    }
+    ///
+    /// Most frames decoded from already buffered input before the decoder
+    /// has to wait for more data.
+    ///
+    pub fn max_pending_frames(&self) -> usize {
+        self.max_pending_frames
+    }
+    ///
+    /// Policy applied once more than `max_pending_frames` frames are pending.
+    ///
+    /// `Drop` decodes and discards the excess frames, `Truncate` discards the
+    /// rest of the buffered input without decoding it.
+    ///
+    pub fn pending_frames_policy(&self) -> ViolationPolicy {
+        self.pending_frames_policy
+    }
+    ///
+    /// Policy applied to unknown commands following IAC.
+    ///
+    /// `Drop` and `Truncate` both discard the command.
+    ///
+    pub fn unknown_command_policy(&self) -> ViolationPolicy {
+        self.unknown_command_policy
+    }
+    ///
+    /// Behaviour when a subnegotiation is interrupted by an invalid command.
+    ///
+    pub fn mode(&self) -> DecoderMode {
+        self.mode
+    }
+}
+
+impl Default for CodecConfiguration {
+    fn default() -> CodecConfiguration {
+        CodecConfiguration::builder().build()
+    }
+}
+
+///
+/// Builder for a [`CodecConfiguration`].
+///
+/// By default subnegotiations are limited to 64 KiB and dropped beyond that,
+/// pending frames are unlimited, unknown commands are dropped and interrupted
+/// subnegotiations are reported.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct CodecConfigurationBuilder {
+    config: CodecConfiguration,
+}
+
+impl CodecConfigurationBuilder {
+    ///
+    /// Limit subnegotiation payloads to `length` bytes, applying `policy` to
+    /// longer ones.
+    ///
+    pub fn max_subnegotiation(
+        mut self,
+        length: usize,
+        policy: ViolationPolicy,
+    ) -> CodecConfigurationBuilder {
+        self.config.max_subnegotiation = length;
+        self.config.subnegotiation_policy = policy;
+        self
+    }
+    ///
+    /// Limit the frames decoded from buffered input to `count`, applying
+    /// `policy` to the rest.
+    ///
+    pub fn max_pending_frames(
+        mut self,
+        count: usize,
+        policy: ViolationPolicy,
+    ) -> CodecConfigurationBuilder {
+        self.config.max_pending_frames = count;
+        self.config.pending_frames_policy = policy;
+        self
+    }
+    ///
+    /// Set the policy applied to unknown commands following IAC.
+    ///
+    pub fn unknown_command(
+        mut self,
+        policy: ViolationPolicy,
+    ) -> CodecConfigurationBuilder {
+        self.config.unknown_command_policy = policy;
+        self
+    }
+    ///
+    /// Set how the decoder resynchronises after an interrupted
+    /// subnegotiation.
+    ///
+    pub fn mode(mut self, mode: DecoderMode) -> CodecConfigurationBuilder {
+        self.config.mode = mode;
+        self
+    }
+    ///
+    /// Fail decoding on every violation, keeping the configured limits.
+    ///
+    pub fn strict(mut self) -> CodecConfigurationBuilder {
+        self.config.subnegotiation_policy = ViolationPolicy::Error;
+        self.config.pending_frames_policy = ViolationPolicy::Error;
+        self.config.unknown_command_policy = ViolationPolicy::Error;
+        self.config.mode = DecoderMode::Strict;
+        self
+    }
+    ///
+    /// Build the `CodecConfiguration`
+    ///
+    pub fn build(self) -> CodecConfiguration {
+        self.config
+    }
+}
+
+impl Default for CodecConfigurationBuilder {
+    fn default() -> CodecConfigurationBuilder {
+        CodecConfigurationBuilder {
+            config: CodecConfiguration {
+                max_subnegotiation: 64 * 1024,
+                subnegotiation_policy: ViolationPolicy::Drop,
+                max_pending_frames: usize::MAX,
+                pending_frames_policy: ViolationPolicy::Drop,
+                unknown_command_policy: ViolationPolicy::Drop,
+                mode: DecoderMode::Report,
+            },
+        }
+    }
+}
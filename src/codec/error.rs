@@ -24,6 +24,12 @@ pub enum DecodeError {
     IOError(io::Error),
     /// An unknown or invalid command was used
     UnknownCommand(u8),
+    /// IAC followed by this byte interrupted a subnegotiation
+    InvalidSubnegotiation(u8),
+    /// A subnegotiation exceeded the configured length
+    SubnegotiationTooLong(usize),
+    /// More frames were pending than the configured limit
+    TooManyPendingFrames(usize),
 }
 
 impl error::Error for DecodeError {
@@ -31,6 +37,9 @@ impl error::Error for DecodeError {
         match self {
             DecodeError::IOError(inner) => Some(inner),
             DecodeError::UnknownCommand(_) => None,
+            DecodeError::InvalidSubnegotiation(_) => None,
+            DecodeError::SubnegotiationTooLong(_) => None,
+            DecodeError::TooManyPendingFrames(_) => None,
         }
     }
 }
@@ -44,6 +53,15 @@ impl fmt::Display for DecodeError {
             DecodeError::UnknownCommand(cmd) => {
                 write!(f, "DecodeError::UnknownCommand({:#X})", cmd)
             }
+            DecodeError::InvalidSubnegotiation(cmd) => {
+                write!(f, "DecodeError::InvalidSubnegotiation({:#X})", cmd)
+            }
+            DecodeError::SubnegotiationTooLong(limit) => {
+                write!(f, "DecodeError::SubnegotiationTooLong({})", limit)
+            }
+            DecodeError::TooManyPendingFrames(limit) => {
+                write!(f, "DecodeError::TooManyPendingFrames({})", limit)
+            }
         }
     }
 }
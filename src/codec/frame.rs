@@ -15,14 +15,15 @@
 //
 
 use crate::codec::TelnetOption;
+use bytes::Bytes;
 
 ///
 /// `TelnetFrame` is a single immutable Frame of data.
 ///
 #[derive(Clone, Debug, PartialEq)]
 pub enum TelnetFrame {
-    /// Telnet Data
-    Data(u8),
+    /// Run of Telnet Data with any IAC escaping removed
+    Data(Bytes),
     /// No Operation
     NoOperation,
     ///End of urgent Data Stream
@@ -41,6 +42,14 @@ pub enum TelnetFrame {
     EraseLine,
     /// End of input for half-duplex connections.
     GoAhead,
+    /// End of File.
+    EndOfFile,
+    /// Suspend the current process.
+    Suspend,
+    /// Abort the current process.
+    Abort,
+    /// End of Record, commonly used to mark prompts.
+    EndOfRecord,
     /// Request to start using specified arguments.
     Do(TelnetOption),
     /// Demand to stop using specified arguments.
@@ -50,5 +59,27 @@ pub enum TelnetFrame {
     /// Reject the proposed arguments.
     Wont(TelnetOption),
     /// Subnegotiation Payload
-    Subnegotiate(TelnetOption, Vec<u8>),
+    Subnegotiate(TelnetOption, Bytes),
+    /// Input discarded by the decoder, never encoded
+    ProtocolViolation(ProtocolViolation),
+}
+
+///
+/// Input the decoder discarded to resynchronise with a misbehaving remote.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtocolViolation {
+    /// Subnegotiation of an option was interrupted by IAC followed by a byte
+    /// other than IAC or SE.
+    InterruptedSubnegotiation {
+        /// Option being subnegotiated
+        option: TelnetOption,
+        /// Byte that followed IAC
+        command: u8,
+        /// Payload received before the interruption
+        arguments: Bytes,
+        /// Bytes discarded after the interruption, up to the IAC SE ending
+        /// the subnegotiation
+        discarded: usize,
+    },
 }
@@ -15,48 +15,37 @@
 //
 
 mod args;
+#[allow(clippy::module_inception)]
 mod codec;
+mod config;
 mod error;
 mod frame;
 mod options;
 
-pub use self::args::TelnetArgument;
+pub use self::args::{Argument, TelnetArgument};
 pub use self::codec::TelnetCodec;
+pub use self::config::{
+    CodecConfiguration, CodecConfigurationBuilder, DecoderMode, ViolationPolicy,
+};
 pub use self::error::DecodeError;
 pub use self::error::EncodeError;
-pub use self::frame::TelnetFrame;
+pub use self::frame::{ProtocolViolation, TelnetFrame};
 pub use self::options::TelnetOption;
 
 #[cfg(test)]
 mod tests {
-    use super::consts;
-    use super::{TelnetCodec, TelnetFrame};
-    use bytes::BytesMut;
+    use super::{TelnetCodec, TelnetFrame, TelnetOption};
+    use crate::consts;
+    use bytes::{Bytes, BytesMut};
     use tokio_util::codec::{Decoder, Encoder};
 
     #[test]
     fn telnet_decode() {
         let mut codec = TelnetCodec::default();
         let mut encoded_input = BytesMut::from(&b"Terminated line\r\n"[..]);
-        let expected_output = vec![
-            TelnetFrame::Data(b'T'),
-            TelnetFrame::Data(b'e'),
-            TelnetFrame::Data(b'r'),
-            TelnetFrame::Data(b'm'),
-            TelnetFrame::Data(b'i'),
-            TelnetFrame::Data(b'n'),
-            TelnetFrame::Data(b'a'),
-            TelnetFrame::Data(b't'),
-            TelnetFrame::Data(b'e'),
-            TelnetFrame::Data(b'd'),
-            TelnetFrame::Data(b' '),
-            TelnetFrame::Data(b'l'),
-            TelnetFrame::Data(b'i'),
-            TelnetFrame::Data(b'n'),
-            TelnetFrame::Data(b'e'),
-            TelnetFrame::Data(b'\r'),
-            TelnetFrame::Data(b'\n'),
-        ];
+        let expected_output = vec![TelnetFrame::Data(Bytes::from_static(
+            b"Terminated line\r\n",
+        ))];
         let mut actual_output = Vec::new();
         while let Some(frame) = codec.decode(&mut encoded_input).unwrap() {
             actual_output.push(frame)
@@ -72,22 +61,8 @@ mod tests {
     fn telnet_encode() {
         let mut codec = TelnetCodec::default();
         let input_frames = vec![
-            TelnetFrame::Data(b'R'),
-            TelnetFrame::Data(b'a'),
-            TelnetFrame::Data(b'w'),
-            TelnetFrame::Data(b' '),
-            TelnetFrame::Data(b'A'),
-            TelnetFrame::Data(b's'),
-            TelnetFrame::Data(b'c'),
-            TelnetFrame::Data(b'i'),
-            TelnetFrame::Data(b'i'),
-            TelnetFrame::Data(b' '),
-            TelnetFrame::Data(b'D'),
-            TelnetFrame::Data(b'a'),
-            TelnetFrame::Data(b't'),
-            TelnetFrame::Data(b'a'),
-            TelnetFrame::Data(b'\r'),
-            TelnetFrame::Data(b'\n'),
+            TelnetFrame::Data(Bytes::from_static(b"Raw ")),
+            TelnetFrame::Data(Bytes::from_static(b"Ascii Data\r\n")),
         ];
         let expected_output = BytesMut::from(&b"Raw Ascii Data\r\n"[..]);
         let mut actual_output = BytesMut::with_capacity(20);
@@ -119,7 +94,7 @@ mod tests {
                 // Command Do Binary
                 consts::IAC,
                 consts::DO,
-                crate::terminal::consts::option::BINARY,
+                consts::option::BINARY,
                 // Data
                 b'P',
                 b'a',
@@ -135,7 +110,7 @@ mod tests {
                 // Command Will Binary
                 consts::IAC,
                 consts::WILL,
-                crate::terminal::consts::option::BINARY,
+                consts::option::BINARY,
                 // Data
                 b'H',
                 b'e',
@@ -149,39 +124,15 @@ mod tests {
         );
         let expected_output = vec![
             // Normal Data
-            TelnetFrame::Data(b'L'),
-            TelnetFrame::Data(b'o'),
-            TelnetFrame::Data(b'g'),
-            TelnetFrame::Data(b'i'),
-            TelnetFrame::Data(b'n'),
-            TelnetFrame::Data(b':'),
-            TelnetFrame::Data(consts::CR),
-            TelnetFrame::Data(consts::LF),
+            TelnetFrame::Data(Bytes::from_static(b"Login:\r\n")),
             // Command Do Binary
-            TelnetFrame::Do(crate::terminal::consts::option::BINARY),
+            TelnetFrame::Do(TelnetOption::TransmitBinary),
             // Data
-            TelnetFrame::Data(b'P'),
-            TelnetFrame::Data(b'a'),
-            TelnetFrame::Data(b's'),
-            TelnetFrame::Data(b's'),
-            TelnetFrame::Data(b'w'),
-            TelnetFrame::Data(b'o'),
-            TelnetFrame::Data(b'r'),
-            TelnetFrame::Data(b'd'),
-            TelnetFrame::Data(b':'),
-            TelnetFrame::Data(consts::CR),
-            TelnetFrame::Data(consts::LF),
+            TelnetFrame::Data(Bytes::from_static(b"Password:\r\n")),
             // Command Will Binary
-            TelnetFrame::Will(crate::terminal::consts::option::BINARY),
+            TelnetFrame::Will(TelnetOption::TransmitBinary),
             // Data
-            TelnetFrame::Data(b'H'),
-            TelnetFrame::Data(b'e'),
-            TelnetFrame::Data(b'l'),
-            TelnetFrame::Data(b'l'),
-            TelnetFrame::Data(b'o'),
-            TelnetFrame::Data(b'!'),
-            TelnetFrame::Data(consts::CR),
-            TelnetFrame::Data(consts::LF),
+            TelnetFrame::Data(Bytes::from_static(b"Hello!\r\n")),
         ];
         let mut actual_output = Vec::new();
         while let Some(frame) = codec.decode(&mut encoded_input).unwrap() {
@@ -190,4 +141,78 @@ mod tests {
 
         assert_eq!(expected_output, actual_output);
     }
+
+    #[test]
+    fn encode_subnegotiation_iac() {
+        let mut codec = TelnetCodec::default();
+        let mut actual_output = BytesMut::new();
+        codec
+            .encode(
+                TelnetFrame::Subnegotiate(
+                    TelnetOption::NAWS,
+                    Bytes::from_static(&[0, consts::IAC, 0, 24]),
+                ),
+                &mut actual_output,
+            )
+            .unwrap();
+        let expected_output = BytesMut::from(
+            &[
+                consts::IAC,
+                consts::SB,
+                consts::option::NAWS,
+                0,
+                consts::IAC,
+                consts::IAC,
+                0,
+                24,
+                consts::IAC,
+                consts::SE,
+            ][..],
+        );
+        assert_eq!(expected_output, actual_output);
+    }
+
+    #[test]
+    fn data_iac_escaping() {
+        let mut codec = TelnetCodec::default();
+        let mut encoded_input = BytesMut::from(
+            &[
+                b'a',
+                consts::IAC,
+                consts::IAC,
+                b'b',
+                consts::IAC,
+                consts::GA,
+            ][..],
+        );
+        let mut actual_output = Vec::new();
+        while let Some(frame) = codec.decode(&mut encoded_input).unwrap() {
+            actual_output.push(frame)
+        }
+        assert_eq!(
+            vec![
+                TelnetFrame::Data(Bytes::from_static(b"a")),
+                TelnetFrame::Data(Bytes::from_static(&[consts::IAC])),
+                TelnetFrame::Data(Bytes::from_static(b"b")),
+                TelnetFrame::GoAhead,
+            ],
+            actual_output
+        );
+
+        let mut actual_output = BytesMut::new();
+        codec
+            .encode(
+                TelnetFrame::Data(Bytes::from_static(&[
+                    b'a',
+                    consts::IAC,
+                    b'b',
+                ])),
+                &mut actual_output,
+            )
+            .unwrap();
+        assert_eq!(
+            BytesMut::from(&[b'a', consts::IAC, consts::IAC, b'b'][..]),
+            actual_output
+        );
+    }
 }
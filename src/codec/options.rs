@@ -19,6 +19,7 @@ use crate::consts;
 ///
 /// [Telnet Terminal Options](https://www.iana.org/assignments/telnet-options/telnet-options.xhtml)
 ///
+#[allow(clippy::tabs_in_doc_comments)]
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum TelnetOption {
@@ -187,6 +187,7 @@ pub mod option {
     pub const EXOPL: u8 = 255;
 
     /// Charset Subnegotiation Tokens
+    #[allow(clippy::zero_prefixed_literal)]
     pub mod charset {
         pub const REQUEST: u8 = 1;
         pub const ACCEPTED: u8 = 02;
@@ -203,12 +204,74 @@ pub mod option {
     }
 
     pub mod new_environ {
+        /// Subnegotiation IS command.
+        pub const IS: u8 = 0;
+        /// Subnegotiation SEND command.
+        pub const SEND: u8 = 1;
+        /// Subnegotiation INFO command.
+        pub const INFO: u8 = 2;
         pub const VAR: u8 = 0;
         pub const VALUE: u8 = 1;
         pub const ESC: u8 = 2;
         pub const USERVAR: u8 = 3;
     }
 
+    pub mod linemode {
+        /// Subnegotiation MODE command.
+        pub const MODE: u8 = 1;
+        /// Subnegotiation FORWARDMASK command.
+        pub const FORWARDMASK: u8 = 2;
+        /// Subnegotiation SLC command.
+        pub const SLC: u8 = 3;
+
+        /// MODE bit for local line editing.
+        pub const EDIT: u8 = 1;
+        /// MODE bit for trapping signals locally.
+        pub const TRAPSIG: u8 = 2;
+        /// MODE bit acknowledging a mode change.
+        pub const MODE_ACK: u8 = 4;
+        /// MODE bit for expanding tabs locally.
+        pub const SOFT_TAB: u8 = 8;
+        /// MODE bit for echoing control characters literally.
+        pub const LIT_ECHO: u8 = 16;
+
+        pub const SLC_SYNCH: u8 = 1;
+        pub const SLC_BRK: u8 = 2;
+        pub const SLC_IP: u8 = 3;
+        pub const SLC_AO: u8 = 4;
+        pub const SLC_AYT: u8 = 5;
+        pub const SLC_EOR: u8 = 6;
+        pub const SLC_ABORT: u8 = 7;
+        pub const SLC_EOF: u8 = 8;
+        pub const SLC_SUSP: u8 = 9;
+        pub const SLC_EC: u8 = 10;
+        pub const SLC_EL: u8 = 11;
+        pub const SLC_EW: u8 = 12;
+        pub const SLC_RP: u8 = 13;
+        pub const SLC_LNEXT: u8 = 14;
+        pub const SLC_XON: u8 = 15;
+        pub const SLC_XOFF: u8 = 16;
+        pub const SLC_FORW1: u8 = 17;
+        pub const SLC_FORW2: u8 = 18;
+
+        /// SLC level for a function that is not supported.
+        pub const SLC_NOSUPPORT: u8 = 0;
+        /// SLC level for a value that will not be changed.
+        pub const SLC_CANTCHANGE: u8 = 1;
+        /// SLC level for a value that may be changed.
+        pub const SLC_VALUE: u8 = 2;
+        /// SLC level asking for the default value.
+        pub const SLC_DEFAULT: u8 = 3;
+        /// Mask selecting the SLC level from the modifiers.
+        pub const SLC_LEVELBITS: u8 = 3;
+        /// SLC modifier flushing output when the character is typed.
+        pub const SLC_FLUSHOUT: u8 = 32;
+        /// SLC modifier flushing input when the character is typed.
+        pub const SLC_FLUSHIN: u8 = 64;
+        /// SLC modifier acknowledging a value.
+        pub const SLC_ACK: u8 = 128;
+    }
+
     pub mod msdp {
         pub const VAR: u8 = 1;
         pub const VAL: u8 = 2;
@@ -223,274 +286,17 @@ pub mod option {
         pub const VAL: u8 = 2;
     }
 
-    pub mod status {
+    pub mod ttype {
         /// Subnegotiation IS command.
         pub const IS: u8 = 0;
         /// Subnegotiation SEND command.
         pub const SEND: u8 = 1;
     }
 
-    ///
-    /// Options this library supports.
-    /// Set to True when implementation exists.
-    /// (Local Support, Remote Support)
-    ///
-    pub static SUPPORT: [(bool, bool); 256] = [
-        (false, false), //   0 - Binary
-        (false, false), //   1 - Echo
-        (false, false), //   2 - RCP
-        (false, false), //   3 - SGA
-        (false, false), //   4 - NAMS
-        (false, false), //   5 - STATUS
-        (false, false), //   6 - TM
-        (false, false), //   7 - RCTE
-        (false, false), //   8 - NAOL
-        (false, false), //   9 - NAOP
-        (false, false), //  10 - NAOCRD
-        (false, false), //  11 - NAOHTS
-        (false, false), //  12 - NAOHTD
-        (false, false), //  13 - NAOFFD
-        (false, false), //  14 - NAOVTS
-        (false, false), //  15 - NAOVTD
-        (false, false), //  16 - NAOLFD
-        (false, false), //  17 - XASCII
-        (false, false), //  18 - LOGOUT
-        (false, false), //  19 - BM
-        (false, false), //  20 - DET
-        (false, false), //  21 - SUPDUP
-        (false, false), //  22 - SUPDUP_OUTPUT
-        (false, false), //  23 - SNDLOC
-        (false, false), //  24 - TTYPE
-        (false, false), //  25 - EOR
-        (false, false), //  26 - TUID
-        (false, false), //  27 - OUTMRK
-        (false, false), //  28 - TTYLOC
-        (false, false), //  29 - OPT3270REGIME
-        (false, false), //  30 - X3PAD
-        (false, false), //  31 - NAWS
-        (false, false), //  32 - TSPEED
-        (false, false), //  33 - LFLOW
-        (false, false), //  34 - LINEMODE
-        (false, false), //  35 - XDISPLOC
-        (false, false), //  36 - OLD_ENVIRONMENT
-        (false, false), //  37 - AUTHENTICATION
-        (false, false), //  38 - ENCRYPTION
-        (false, false), //  39 - NEW_ENVIRONMENT
-        (false, false), //  40 - TN3270E
-        (false, false), //  41 - XAUTH
-        (false, false), //  42 - CHARSET
-        (false, false), //  43 - TRSP
-        (false, false), //  44 - CPCO
-        (false, false), //  45 - TSLE
-        (false, false), //  46 - START_TLS
-        (false, false), //  47 - KERMIT
-        (false, false), //  48 - SENDURL
-        (false, false), //  49 - FORWARDX
-        (false, false), //  50 -
-        (false, false), //  51 -
-        (false, false), //  52 -
-        (false, false), //  53 -
-        (false, false), //  54 -
-        (false, false), //  55 -
-        (false, false), //  56 -
-        (false, false), //  57 -
-        (false, false), //  58 -
-        (false, false), //  59 -
-        (false, false), //  60 -
-        (false, false), //  61 -
-        (false, false), //  62 -
-        (false, false), //  63 -
-        (false, false), //  64 -
-        (false, false), //  65 -
-        (false, false), //  66 -
-        (false, false), //  67 -
-        (false, false), //  68 -
-        (false, false), //  69 - MSDP
-        (false, false), //  70 - MSSP
-        (false, false), //  71 -
-        (false, false), //  72 -
-        (false, false), //  73 -
-        (false, false), //  74 -
-        (false, false), //  75 -
-        (false, false), //  76 -
-        (false, false), //  77 -
-        (false, false), //  78 -
-        (false, false), //  79 -
-        (false, false), //  80 -
-        (false, false), //  81 -
-        (false, false), //  82 -
-        (false, false), //  83 -
-        (false, false), //  84 -
-        (false, false), //  85 - Compress1
-        (false, false), //  86 - Compress2
-        (false, false), //  87 -
-        (false, false), //  88 -
-        (false, false), //  89 -
-        (false, false), //  90 -
-        (false, false), //  91 -
-        (false, false), //  92 -
-        (false, false), //  93 - ZMP
-        (false, false), //  94 -
-        (false, false), //  95 -
-        (false, false), //  96 -
-        (false, false), //  97 -
-        (false, false), //  98 -
-        (false, false), //  99 -
-        (false, false), // 100 -
-        (false, false), // 101 -
-        (false, false), // 102 -
-        (false, false), // 103 -
-        (false, false), // 104 -
-        (false, false), // 105 -
-        (false, false), // 106 -
-        (false, false), // 107 -
-        (false, false), // 108 -
-        (false, false), // 109 -
-        (false, false), // 110 -
-        (false, false), // 111 -
-        (false, false), // 112 -
-        (false, false), // 113 -
-        (false, false), // 114 -
-        (false, false), // 115 -
-        (false, false), // 116 -
-        (false, false), // 117 -
-        (false, false), // 118 -
-        (false, false), // 119 -
-        (false, false), // 120 -
-        (false, false), // 121 -
-        (false, false), // 122 -
-        (false, false), // 123 -
-        (false, false), // 124 -
-        (false, false), // 125 -
-        (false, false), // 126 -
-        (false, false), // 127 -
-        (false, false), // 128 -
-        (false, false), // 129 -
-        (false, false), // 130 -
-        (false, false), // 131 -
-        (false, false), // 132 -
-        (false, false), // 133 -
-        (false, false), // 134 -
-        (false, false), // 135 -
-        (false, false), // 136 -
-        (false, false), // 137 -
-        (false, false), // 138 - PRAGMA_LOGIN
-        (false, false), // 139 - SSPI_LOGIN
-        (false, false), // 140 - PRAGMA_HEARTBEAT
-        (false, false), // 141 -
-        (false, false), // 142 -
-        (false, false), // 143 -
-        (false, false), // 144 -
-        (false, false), // 145 -
-        (false, false), // 146 -
-        (false, false), // 147 -
-        (false, false), // 148 -
-        (false, false), // 149 -
-        (false, false), // 150 -
-        (false, false), // 151 -
-        (false, false), // 152 -
-        (false, false), // 153 -
-        (false, false), // 154 -
-        (false, false), // 155 -
-        (false, false), // 156 -
-        (false, false), // 157 -
-        (false, false), // 158 -
-        (false, false), // 159 -
-        (false, false), // 160 -
-        (false, false), // 161 -
-        (false, false), // 162 -
-        (false, false), // 163 -
-        (false, false), // 164 -
-        (false, false), // 165 -
-        (false, false), // 166 -
-        (false, false), // 167 -
-        (false, false), // 168 -
-        (false, false), // 169 -
-        (false, false), // 170 -
-        (false, false), // 171 -
-        (false, false), // 172 -
-        (false, false), // 173 -
-        (false, false), // 174 -
-        (false, false), // 175 -
-        (false, false), // 176 -
-        (false, false), // 177 -
-        (false, false), // 178 -
-        (false, false), // 189 -
-        (false, false), // 180 -
-        (false, false), // 181 -
-        (false, false), // 182 -
-        (false, false), // 183 -
-        (false, false), // 184 -
-        (false, false), // 185 -
-        (false, false), // 186 -
-        (false, false), // 187 -
-        (false, false), // 188 -
-        (false, false), // 189 -
-        (false, false), // 190 -
-        (false, false), // 191 -
-        (false, false), // 192 -
-        (false, false), // 193 -
-        (false, false), // 194 -
-        (false, false), // 195 -
-        (false, false), // 196 -
-        (false, false), // 197 -
-        (false, false), // 198 -
-        (false, false), // 199 -
-        (false, false), // 200 -
-        (false, false), // 201 - GMCP
-        (false, false), // 202 -
-        (false, false), // 203 -
-        (false, false), // 204 -
-        (false, false), // 205 -
-        (false, false), // 206 -
-        (false, false), // 207 -
-        (false, false), // 208 -
-        (false, false), // 209 -
-        (false, false), // 210 -
-        (false, false), // 211 -
-        (false, false), // 212 -
-        (false, false), // 213 -
-        (false, false), // 214 -
-        (false, false), // 215 -
-        (false, false), // 216 -
-        (false, false), // 217 -
-        (false, false), // 218 -
-        (false, false), // 219 -
-        (false, false), // 220 -
-        (false, false), // 221 -
-        (false, false), // 222 -
-        (false, false), // 223 -
-        (false, false), // 224 -
-        (false, false), // 225 -
-        (false, false), // 226 -
-        (false, false), // 227 -
-        (false, false), // 228 -
-        (false, false), // 229 -
-        (false, false), // 230 -
-        (false, false), // 231 -
-        (false, false), // 232 -
-        (false, false), // 233 -
-        (false, false), // 234 -
-        (false, false), // 235 -
-        (false, false), // 236 -
-        (false, false), // 237 -
-        (false, false), // 238 -
-        (false, false), // 239 -
-        (false, false), // 240 -
-        (false, false), // 241 -
-        (false, false), // 242 -
-        (false, false), // 243 -
-        (false, false), // 244 -
-        (false, false), // 245 -
-        (false, false), // 246 -
-        (false, false), // 247 -
-        (false, false), // 248 -
-        (false, false), // 249 -
-        (false, false), // 250 -
-        (false, false), // 251 -
-        (false, false), // 252 -
-        (false, false), // 253 -
-        (false, false), // 254 -
-        (false, false), // 255 - EXOPL
-    ];
+    pub mod status {
+        /// Subnegotiation IS command.
+        pub const IS: u8 = 0;
+        /// Subnegotiation SEND command.
+        pub const SEND: u8 = 1;
+    }
 }
@@ -15,6 +15,6 @@
 //
 
 ///
-///
+/// Telnet Server
 ///
 pub struct TelnetServer {}
@@ -15,6 +15,6 @@
 //
 
 ///
-///
+/// Telnet Socket
 ///
 pub struct TelnetSocket {}
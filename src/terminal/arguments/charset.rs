@@ -0,0 +1,179 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::codec::Argument;
+use crate::consts;
+use bytes::{Buf, BufMut};
+
+///
+/// Marker preceding the version of a translation table offered with a
+/// `REQUEST`.
+///
+const TTABLE: &[u8] = b"[TTABLE]";
+
+///
+/// `Charset` contains the CHARSET subnegotiation payload.
+/// [RFC2066](http://www.iana.org/go/rfc2066)
+///
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Charset {
+    /// Character sets the sender can use, in order of preference
+    Request(Vec<String>),
+    /// Character set chosen from a `Request`
+    Accepted(String),
+    /// None of the requested character sets are acceptable
+    Rejected,
+    /// Translation table for a character set
+    TTableIs(Vec<u8>),
+    /// Translation table was not acceptable
+    TTableRejected,
+    /// Translation table was received
+    TTableAck,
+    /// Translation table was corrupted, send it again
+    TTableNak,
+}
+
+impl Argument for Charset {
+    fn len(&self) -> usize {
+        1 + match self {
+            // One separator before each name
+            Charset::Request(names) => {
+                names.iter().map(|name| 1 + name.len()).sum()
+            }
+            Charset::Accepted(name) => name.len(),
+            Charset::TTableIs(table) => table.len(),
+            _ => 0,
+        }
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        match self {
+            Charset::Request(names) => {
+                dst.put_u8(consts::option::charset::REQUEST);
+                for name in names {
+                    dst.put_u8(b' ');
+                    dst.put_slice(name.as_bytes());
+                }
+            }
+            Charset::Accepted(name) => {
+                dst.put_u8(consts::option::charset::ACCEPTED);
+                dst.put_slice(name.as_bytes());
+            }
+            Charset::Rejected => dst.put_u8(consts::option::charset::REJECTED),
+            Charset::TTableIs(table) => {
+                dst.put_u8(consts::option::charset::TTABLE_IS);
+                dst.put_slice(table);
+            }
+            Charset::TTableRejected => {
+                dst.put_u8(consts::option::charset::TTABLE_REJECTED)
+            }
+            Charset::TTableAck => {
+                dst.put_u8(consts::option::charset::TTABLE_ACK)
+            }
+            Charset::TTableNak => {
+                dst.put_u8(consts::option::charset::TTABLE_NAK)
+            }
+        }
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        if !src.has_remaining() {
+            return None;
+        }
+        let command = src.get_u8();
+        let arguments = src.to_bytes();
+        let name = || {
+            if arguments.is_empty() || !arguments.is_ascii() {
+                return None;
+            }
+            Some(String::from_utf8_lossy(&arguments).into_owned())
+        };
+        match command {
+            consts::option::charset::REQUEST => {
+                let mut names = &arguments[..];
+                // Translation tables are never used, skip the version.
+                if names.starts_with(TTABLE) {
+                    names = names.get(TTABLE.len() + 1..)?;
+                }
+                let (separator, names) = names.split_first()?;
+                if !names.is_ascii() {
+                    return None;
+                }
+                Some(Charset::Request(
+                    names
+                        .split(|byte| byte == separator)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| String::from_utf8_lossy(name).into_owned())
+                        .collect(),
+                ))
+            }
+            consts::option::charset::ACCEPTED => name().map(Charset::Accepted),
+            consts::option::charset::REJECTED => Some(Charset::Rejected),
+            consts::option::charset::TTABLE_IS => {
+                Some(Charset::TTableIs(arguments.to_vec()))
+            }
+            consts::option::charset::TTABLE_REJECTED => {
+                Some(Charset::TTableRejected)
+            }
+            consts::option::charset::TTABLE_ACK => Some(Charset::TTableAck),
+            consts::option::charset::TTABLE_NAK => Some(Charset::TTableNak),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Charset;
+    use crate::codec::Argument;
+
+    #[test]
+    fn charset_round_trip() {
+        let messages = [
+            Charset::Request(vec!["UTF-8".to_string(), "CP437".to_string()]),
+            Charset::Accepted("ISO-8859-1".to_string()),
+            Charset::Rejected,
+            Charset::TTableIs(vec![1, 2, 3]),
+            Charset::TTableAck,
+        ];
+        for message in messages.iter() {
+            let mut arguments = Vec::new();
+            message.encode(&mut arguments);
+            assert_eq!(arguments.len(), message.len());
+            assert_eq!(
+                Charset::decode(&mut &arguments[..]).as_ref(),
+                Some(message)
+            );
+        }
+    }
+
+    #[test]
+    fn charset_request_separators() {
+        let request = |names: &[&str]| {
+            Some(Charset::Request(
+                names.iter().map(|n| n.to_string()).collect(),
+            ))
+        };
+        assert_eq!(
+            Charset::decode(&mut &b"\x01;UTF-8;ISO-8859-1"[..]),
+            request(&["UTF-8", "ISO-8859-1"])
+        );
+        assert_eq!(
+            Charset::decode(&mut &b"\x01[TTABLE]\x01 KOI8-R"[..]),
+            request(&["KOI8-R"])
+        );
+        assert_eq!(Charset::decode(&mut &b"\x01"[..]), None);
+        assert_eq!(Charset::decode(&mut &b"\x02"[..]), None);
+    }
+}
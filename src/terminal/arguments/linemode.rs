@@ -0,0 +1,518 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::codec::Argument;
+use crate::consts;
+use crate::consts::option::linemode as lm;
+use bytes::{Buf, BufMut};
+use std::collections::BTreeMap;
+
+///
+/// Longest FORWARDMASK, one bit for each of the 256 byte values.
+///
+const MAX_FORWARD_MASK: usize = 32;
+
+///
+/// Editing performed by the client while LINEMODE is enabled.
+///
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LinemodeMode {
+    /// Client edits lines locally and sends them when complete
+    pub edit: bool,
+    /// Client turns interrupt and suspend characters into Telnet commands
+    pub trapsig: bool,
+    /// Client expands tabs into spaces
+    pub soft_tab: bool,
+    /// Client echoes control characters literally
+    pub lit_echo: bool,
+}
+
+impl LinemodeMode {
+    ///
+    /// Decode a MODE mask, ignoring the acknowledgment and unknown bits.
+    ///
+    pub fn from_bits(bits: u8) -> LinemodeMode {
+        LinemodeMode {
+            edit: bits & lm::EDIT != 0,
+            trapsig: bits & lm::TRAPSIG != 0,
+            soft_tab: bits & lm::SOFT_TAB != 0,
+            lit_echo: bits & lm::LIT_ECHO != 0,
+        }
+    }
+    ///
+    /// Encode the mode as a MODE mask.
+    ///
+    pub fn bits(&self) -> u8 {
+        let flags = [
+            (self.edit, lm::EDIT),
+            (self.trapsig, lm::TRAPSIG),
+            (self.soft_tab, lm::SOFT_TAB),
+            (self.lit_echo, lm::LIT_ECHO),
+        ];
+        flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .fold(0, |bits, (_, flag)| bits | flag)
+    }
+}
+
+///
+/// Special character functions that may be mapped by SLC.
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SlcFunction {
+    /// Synch, sent as urgent data
+    Synch,
+    /// Break
+    Break,
+    /// Interrupt Process
+    InterruptProcess,
+    /// Abort Output
+    AbortOutput,
+    /// Are You There
+    AreYouThere,
+    /// End of Record
+    EndOfRecord,
+    /// Abort
+    Abort,
+    /// End of File
+    EndOfFile,
+    /// Suspend
+    Suspend,
+    /// Erase Character
+    EraseCharacter,
+    /// Erase Line
+    EraseLine,
+    /// Erase Word
+    EraseWord,
+    /// Reprint Line
+    Reprint,
+    /// Literal Next
+    LiteralNext,
+    /// Resume Output
+    Xon,
+    /// Suspend Output
+    Xoff,
+    /// First Forwarding Character
+    Forward1,
+    /// Second Forwarding Character
+    Forward2,
+    /// Function this implementation does not know about
+    Unknown(u8),
+}
+
+impl From<u8> for SlcFunction {
+    fn from(byte: u8) -> SlcFunction {
+        match byte {
+            lm::SLC_SYNCH => SlcFunction::Synch,
+            lm::SLC_BRK => SlcFunction::Break,
+            lm::SLC_IP => SlcFunction::InterruptProcess,
+            lm::SLC_AO => SlcFunction::AbortOutput,
+            lm::SLC_AYT => SlcFunction::AreYouThere,
+            lm::SLC_EOR => SlcFunction::EndOfRecord,
+            lm::SLC_ABORT => SlcFunction::Abort,
+            lm::SLC_EOF => SlcFunction::EndOfFile,
+            lm::SLC_SUSP => SlcFunction::Suspend,
+            lm::SLC_EC => SlcFunction::EraseCharacter,
+            lm::SLC_EL => SlcFunction::EraseLine,
+            lm::SLC_EW => SlcFunction::EraseWord,
+            lm::SLC_RP => SlcFunction::Reprint,
+            lm::SLC_LNEXT => SlcFunction::LiteralNext,
+            lm::SLC_XON => SlcFunction::Xon,
+            lm::SLC_XOFF => SlcFunction::Xoff,
+            lm::SLC_FORW1 => SlcFunction::Forward1,
+            lm::SLC_FORW2 => SlcFunction::Forward2,
+            byte => SlcFunction::Unknown(byte),
+        }
+    }
+}
+
+impl From<SlcFunction> for u8 {
+    fn from(function: SlcFunction) -> u8 {
+        match function {
+            SlcFunction::Synch => lm::SLC_SYNCH,
+            SlcFunction::Break => lm::SLC_BRK,
+            SlcFunction::InterruptProcess => lm::SLC_IP,
+            SlcFunction::AbortOutput => lm::SLC_AO,
+            SlcFunction::AreYouThere => lm::SLC_AYT,
+            SlcFunction::EndOfRecord => lm::SLC_EOR,
+            SlcFunction::Abort => lm::SLC_ABORT,
+            SlcFunction::EndOfFile => lm::SLC_EOF,
+            SlcFunction::Suspend => lm::SLC_SUSP,
+            SlcFunction::EraseCharacter => lm::SLC_EC,
+            SlcFunction::EraseLine => lm::SLC_EL,
+            SlcFunction::EraseWord => lm::SLC_EW,
+            SlcFunction::Reprint => lm::SLC_RP,
+            SlcFunction::LiteralNext => lm::SLC_LNEXT,
+            SlcFunction::Xon => lm::SLC_XON,
+            SlcFunction::Xoff => lm::SLC_XOFF,
+            SlcFunction::Forward1 => lm::SLC_FORW1,
+            SlcFunction::Forward2 => lm::SLC_FORW2,
+            SlcFunction::Unknown(byte) => byte,
+        }
+    }
+}
+
+///
+/// How firmly a special character is mapped.
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SlcLevel {
+    /// Function is not supported
+    NoSupport,
+    /// Value is fixed and will not be changed
+    CantChange,
+    /// Value is in use and may be changed
+    Value,
+    /// Use the default value
+    Default,
+}
+
+impl From<u8> for SlcLevel {
+    fn from(modifiers: u8) -> SlcLevel {
+        match modifiers & lm::SLC_LEVELBITS {
+            lm::SLC_NOSUPPORT => SlcLevel::NoSupport,
+            lm::SLC_CANTCHANGE => SlcLevel::CantChange,
+            lm::SLC_VALUE => SlcLevel::Value,
+            _ => SlcLevel::Default,
+        }
+    }
+}
+
+impl From<SlcLevel> for u8 {
+    fn from(level: SlcLevel) -> u8 {
+        match level {
+            SlcLevel::NoSupport => lm::SLC_NOSUPPORT,
+            SlcLevel::CantChange => lm::SLC_CANTCHANGE,
+            SlcLevel::Value => lm::SLC_VALUE,
+            SlcLevel::Default => lm::SLC_DEFAULT,
+        }
+    }
+}
+
+///
+/// One entry of the special character table.
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SlcTriplet {
+    /// Function being mapped
+    pub function: SlcFunction,
+    /// How firmly the character is mapped
+    pub level: SlcLevel,
+    /// Entry acknowledges a value sent by the other side
+    pub ack: bool,
+    /// Typing the character flushes pending input
+    pub flush_in: bool,
+    /// Typing the character flushes pending output
+    pub flush_out: bool,
+    /// Character mapped to the function
+    pub value: u8,
+}
+
+impl SlcTriplet {
+    ///
+    /// Create a new `SlcTriplet` without any flags
+    ///
+    pub fn new(
+        function: SlcFunction,
+        level: SlcLevel,
+        value: u8,
+    ) -> SlcTriplet {
+        SlcTriplet {
+            function,
+            level,
+            ack: false,
+            flush_in: false,
+            flush_out: false,
+            value,
+        }
+    }
+    ///
+    /// Whether `other` maps the same character at the same level.
+    ///
+    pub fn same_mapping(&self, other: &SlcTriplet) -> bool {
+        self.level == other.level && self.value == other.value
+    }
+    fn modifiers(&self) -> u8 {
+        let flags = [
+            (self.ack, lm::SLC_ACK),
+            (self.flush_in, lm::SLC_FLUSHIN),
+            (self.flush_out, lm::SLC_FLUSHOUT),
+        ];
+        flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .fold(u8::from(self.level), |bits, (_, flag)| bits | flag)
+    }
+    fn from_bytes(function: u8, modifiers: u8, value: u8) -> SlcTriplet {
+        SlcTriplet {
+            function: SlcFunction::from(function),
+            level: SlcLevel::from(modifiers),
+            ack: modifiers & lm::SLC_ACK != 0,
+            flush_in: modifiers & lm::SLC_FLUSHIN != 0,
+            flush_out: modifiers & lm::SLC_FLUSHOUT != 0,
+            value,
+        }
+    }
+}
+
+///
+/// Special character table, keyed by function.
+///
+pub type SlcTable = BTreeMap<SlcFunction, SlcTriplet>;
+
+///
+/// Special characters of a typical Unix terminal, with the functions that
+/// have no usual character marked as not supported.
+///
+pub fn default_slc_table() -> SlcTable {
+    let characters = [
+        (SlcFunction::Synch, None),
+        (SlcFunction::Break, None),
+        (SlcFunction::InterruptProcess, Some(0x03)),
+        (SlcFunction::AbortOutput, Some(0x0F)),
+        (SlcFunction::AreYouThere, Some(0x14)),
+        (SlcFunction::EndOfRecord, None),
+        (SlcFunction::Abort, Some(0x1C)),
+        (SlcFunction::EndOfFile, Some(0x04)),
+        (SlcFunction::Suspend, Some(0x1A)),
+        (SlcFunction::EraseCharacter, Some(0x7F)),
+        (SlcFunction::EraseLine, Some(0x15)),
+        (SlcFunction::EraseWord, Some(0x17)),
+        (SlcFunction::Reprint, Some(0x12)),
+        (SlcFunction::LiteralNext, Some(0x16)),
+        (SlcFunction::Xon, Some(0x11)),
+        (SlcFunction::Xoff, Some(0x13)),
+        (SlcFunction::Forward1, None),
+        (SlcFunction::Forward2, None),
+    ];
+    characters
+        .iter()
+        .map(|&(function, value)| {
+            let triplet = match value {
+                Some(value) => {
+                    let mut triplet =
+                        SlcTriplet::new(function, SlcLevel::Value, value);
+                    // Signals discard whatever is still in flight.
+                    triplet.flush_in = matches!(
+                        function,
+                        SlcFunction::InterruptProcess
+                            | SlcFunction::Abort
+                            | SlcFunction::Suspend
+                    );
+                    triplet.flush_out = triplet.flush_in
+                        || function == SlcFunction::AbortOutput;
+                    triplet
+                }
+                None => SlcTriplet::new(function, SlcLevel::NoSupport, 0),
+            };
+            (function, triplet)
+        })
+        .collect()
+}
+
+///
+/// FORWARDMASK negotiation, carried inside the LINEMODE subnegotiation.
+///
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ForwardMask {
+    /// Server asks the client to forward its buffer whenever a character
+    /// whose bit is set in the mask is typed
+    Do(Vec<u8>),
+    /// Server no longer wants a forward mask used
+    Dont,
+    /// Client uses the forward mask
+    Will,
+    /// Client does not use a forward mask
+    Wont,
+}
+
+///
+/// `Linemode` contains the LINEMODE subnegotiation payload.
+/// [RFC1184](http://www.iana.org/go/rfc1184)
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Linemode {
+    /// Set or acknowledge the editing mode
+    Mode {
+        /// Requested mode
+        mode: LinemodeMode,
+        /// Message acknowledges a requested mode
+        ack: bool,
+    },
+    /// Forward mask negotiation
+    ForwardMask(ForwardMask),
+    /// Special character table entries
+    Slc(Vec<SlcTriplet>),
+}
+
+impl Argument for Linemode {
+    fn len(&self) -> usize {
+        match self {
+            Linemode::Mode { .. } => 2,
+            Linemode::ForwardMask(ForwardMask::Do(mask)) => 2 + mask.len(),
+            Linemode::ForwardMask(_) => 2,
+            Linemode::Slc(triplets) => 1 + 3 * triplets.len(),
+        }
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        match self {
+            Linemode::Mode { mode, ack } => {
+                dst.put_u8(lm::MODE);
+                dst.put_u8(mode.bits() | if *ack { lm::MODE_ACK } else { 0 });
+            }
+            Linemode::ForwardMask(forward_mask) => {
+                dst.put_u8(match forward_mask {
+                    ForwardMask::Do(_) => consts::DO,
+                    ForwardMask::Dont => consts::DONT,
+                    ForwardMask::Will => consts::WILL,
+                    ForwardMask::Wont => consts::WONT,
+                });
+                dst.put_u8(lm::FORWARDMASK);
+                if let ForwardMask::Do(mask) = forward_mask {
+                    dst.put_slice(mask);
+                }
+            }
+            Linemode::Slc(triplets) => {
+                dst.put_u8(lm::SLC);
+                for triplet in triplets {
+                    dst.put_u8(u8::from(triplet.function));
+                    dst.put_u8(triplet.modifiers());
+                    dst.put_u8(triplet.value);
+                }
+            }
+        }
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        if !src.has_remaining() {
+            return None;
+        }
+        match src.get_u8() {
+            lm::MODE if src.remaining() == 1 => {
+                let bits = src.get_u8();
+                Some(Linemode::Mode {
+                    mode: LinemodeMode::from_bits(bits),
+                    ack: bits & lm::MODE_ACK != 0,
+                })
+            }
+            lm::SLC if src.remaining().is_multiple_of(3) => {
+                let mut triplets = Vec::with_capacity(src.remaining() / 3);
+                while src.has_remaining() {
+                    let function = src.get_u8();
+                    let modifiers = src.get_u8();
+                    let value = src.get_u8();
+                    triplets.push(SlcTriplet::from_bytes(
+                        function, modifiers, value,
+                    ));
+                }
+                Some(Linemode::Slc(triplets))
+            }
+            command @ consts::WILL..=consts::DONT => {
+                if !src.has_remaining() || src.get_u8() != lm::FORWARDMASK {
+                    return None;
+                }
+                let forward_mask = match command {
+                    consts::DO if src.remaining() <= MAX_FORWARD_MASK => {
+                        ForwardMask::Do(src.to_bytes().to_vec())
+                    }
+                    consts::DONT => ForwardMask::Dont,
+                    consts::WILL => ForwardMask::Will,
+                    consts::WONT => ForwardMask::Wont,
+                    _ => return None,
+                };
+                if command != consts::DO && src.has_remaining() {
+                    return None;
+                }
+                Some(Linemode::ForwardMask(forward_mask))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        default_slc_table, ForwardMask, Linemode, LinemodeMode, SlcFunction,
+        SlcLevel, SlcTriplet,
+    };
+    use crate::codec::Argument;
+
+    #[test]
+    fn linemode_round_trip() {
+        let mut interrupt =
+            SlcTriplet::new(SlcFunction::InterruptProcess, SlcLevel::Value, 3);
+        interrupt.ack = true;
+        interrupt.flush_in = true;
+        let messages = [
+            Linemode::Mode {
+                mode: LinemodeMode {
+                    edit: true,
+                    trapsig: true,
+                    ..LinemodeMode::default()
+                },
+                ack: true,
+            },
+            Linemode::ForwardMask(ForwardMask::Do(vec![0x00, 0x24])),
+            Linemode::ForwardMask(ForwardMask::Wont),
+            Linemode::Slc(vec![
+                interrupt,
+                SlcTriplet::new(SlcFunction::Unknown(30), SlcLevel::Default, 0),
+            ]),
+            Linemode::Slc(default_slc_table().values().copied().collect()),
+        ];
+        for message in messages.iter() {
+            let mut arguments = Vec::new();
+            message.encode(&mut arguments);
+            assert_eq!(arguments.len(), message.len());
+            assert_eq!(
+                Linemode::decode(&mut &arguments[..]).as_ref(),
+                Some(message)
+            );
+        }
+    }
+
+    #[test]
+    fn linemode_wire_format() {
+        let mut arguments = Vec::new();
+        Linemode::Mode {
+            mode: LinemodeMode::from_bits(0x1B),
+            ack: false,
+        }
+        .encode(&mut arguments);
+        assert_eq!(arguments, [1, 0x1B]);
+
+        // SLC IP with VALUE, FLUSHIN and FLUSHOUT set to ^C
+        assert_eq!(
+            Linemode::decode(&mut &[3, 3, 0x62, 3][..]),
+            Some(Linemode::Slc(vec![SlcTriplet {
+                function: SlcFunction::InterruptProcess,
+                level: SlcLevel::Value,
+                ack: false,
+                flush_in: true,
+                flush_out: true,
+                value: 3,
+            }]))
+        );
+        assert_eq!(Linemode::decode(&mut &[3, 3, 2][..]), None);
+        assert_eq!(Linemode::decode(&mut &[1][..]), None);
+        assert_eq!(Linemode::decode(&mut &[251, 2, 0][..]), None);
+        assert_eq!(
+            Linemode::decode(&mut &[253, 2][..]),
+            Some(Linemode::ForwardMask(ForwardMask::Do(Vec::new())))
+        );
+    }
+}
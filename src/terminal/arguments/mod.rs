@@ -14,12 +14,14 @@
 // limitations under the License.
 //
 
-pub use self::args::TelnetArgument;
-pub use self::manager::{Action, Manager};
-pub use self::options::TelnetOption;
-
+pub mod charset;
+pub mod linemode;
 pub mod msdp;
 pub mod mssp;
 pub mod naocrd;
 pub mod naohts;
+pub mod naws;
+pub mod new_environ;
+pub mod old_environ;
 pub mod status;
+pub mod ttype;
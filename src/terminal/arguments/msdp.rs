@@ -34,6 +34,7 @@ impl MudServerData {
     ///
     /// Get Encoded Length of `MudServerData`
     ///
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         let mut length = 0;
         for (key, value) in &self.0 {
@@ -49,9 +50,9 @@ impl MudServerData {
     ///
     pub fn encode<T: BufMut>(&self, dst: &mut T) {
         for (key, value) in &self.0 {
-            dst.put_u8(consts::negotiation::msdp::VAR);
-            dst.put(key);
-            dst.put_u8(consts::negotiation::msdp::VAL);
+            dst.put_u8(consts::option::msdp::VAR);
+            dst.put_slice(key.as_bytes());
+            dst.put_u8(consts::option::msdp::VAL);
             value.encode(dst);
         }
     }
@@ -91,6 +92,7 @@ impl MudServerDataValue {
     ///
     /// Get Encoded Length of `MudServerDataValue`
     ///
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         let mut length = 0;
         match self {
@@ -124,25 +126,25 @@ impl MudServerDataValue {
     pub fn encode<T: BufMut>(&self, dst: &mut T) {
         match self {
             MudServerDataValue::String(string) => {
-                dst.put(string);
+                dst.put_slice(string.as_bytes());
             }
             MudServerDataValue::Array(array) => {
-                dst.put_u8(consts::negotiation::msdp::ARRAY_OPEN);
+                dst.put_u8(consts::option::msdp::ARRAY_OPEN);
                 for value in array {
-                    dst.put_u8(consts::negotiation::msdp::VAL);
+                    dst.put_u8(consts::option::msdp::VAL);
                     value.encode(dst);
                 }
-                dst.put_u8(consts::negotiation::msdp::ARRAY_CLOSE);
+                dst.put_u8(consts::option::msdp::ARRAY_CLOSE);
             }
             MudServerDataValue::Table(table) => {
-                dst.put_u8(consts::negotiation::msdp::TABLE_OPEN);
+                dst.put_u8(consts::option::msdp::TABLE_OPEN);
                 for (key, value) in table {
-                    dst.put_u8(consts::negotiation::msdp::VAR);
-                    dst.put(key);
-                    dst.put_u8(consts::negotiation::msdp::VAL);
+                    dst.put_u8(consts::option::msdp::VAR);
+                    dst.put_slice(key.as_bytes());
+                    dst.put_u8(consts::option::msdp::VAL);
                     value.encode(dst);
                 }
-                dst.put_u8(consts::negotiation::msdp::TABLE_CLOSE);
+                dst.put_u8(consts::option::msdp::TABLE_CLOSE);
             }
         }
     }
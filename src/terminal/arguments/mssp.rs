@@ -34,6 +34,7 @@ impl MudServerStatus {
     ///
     /// Get Encoded Length of `MudServerStatus`
     ///
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         let mut length = 0;
         for (key, values) in &self.0 {
@@ -51,31 +52,39 @@ impl MudServerStatus {
     ///
     pub fn encode<T: BufMut>(&self, dst: &mut T) {
         for (key, values) in &self.0 {
-            dst.put_u8(consts::negotiation::mssp::VAR);
-            dst.put(
+            dst.put_u8(consts::option::mssp::VAR);
+            dst.put_slice(
                 key.chars()
                     .filter(|ch| {
                         *ch != consts::NUL as char
                             && *ch != consts::IAC as char
-                            && *ch != consts::negotiation::mssp::VAR as char
-                            && *ch != consts::negotiation::mssp::VAL as char
+                            && *ch != consts::option::mssp::VAR as char
+                            && *ch != consts::option::mssp::VAL as char
                     })
-                    .collect::<String>(),
+                    .collect::<String>()
+                    .as_bytes(),
             );
             for value in values {
-                dst.put_u8(consts::negotiation::mssp::VAL);
-                dst.put(
+                dst.put_u8(consts::option::mssp::VAL);
+                dst.put_slice(
                     value
                         .chars()
                         .filter(|ch| {
                             *ch != consts::NUL as char
                                 && *ch != consts::IAC as char
-                                && *ch != consts::negotiation::mssp::VAR as char
-                                && *ch != consts::negotiation::mssp::VAL as char
+                                && *ch != consts::option::mssp::VAR as char
+                                && *ch != consts::option::mssp::VAL as char
                         })
-                        .collect::<String>(),
+                        .collect::<String>()
+                        .as_bytes(),
                 );
             }
         }
     }
 }
+
+impl Default for MudServerStatus {
+    fn default() -> MudServerStatus {
+        MudServerStatus::new()
+    }
+}
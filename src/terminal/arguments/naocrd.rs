@@ -28,17 +28,18 @@ pub enum NAOCRD {
 }
 
 impl NAOCRD {
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         2
     }
     pub fn encode<T: BufMut>(&self, dst: &mut T) {
         match *self {
             NAOCRD::Sender(value) => {
-                dst.put_u8(consts::negotiation::naocrd::DS);
+                dst.put_u8(consts::option::naocrd::DS);
                 dst.put_u8(value);
             }
             NAOCRD::Receiver(value) => {
-                dst.put_u8(consts::negotiation::naocrd::DS);
+                dst.put_u8(consts::option::naocrd::DS);
                 dst.put_u8(value);
             }
             NAOCRD::Unknown(side, value) => {
@@ -29,14 +29,15 @@ impl NAOHTS {
     ///
     /// Get Encoded Length of `NAOHTS`
     ///
-    pub(crate) fn len(&self) -> usize {
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
         self.0.len()
     }
     ///
     /// Encode `NAOHTS` to `BufMut`
     ///
-    pub(crate) fn encode<T: BufMut>(&self, dst: &mut T) {
-        dst.put_u8(consts::negotiation::NAOHTS);
-        dst.put(self.0.clone());
+    pub fn encode<T: BufMut>(&self, dst: &mut T) {
+        dst.put_u8(consts::option::NAOHTS);
+        dst.put_slice(&self.0);
     }
 }
@@ -0,0 +1,117 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::codec::Argument;
+use bytes::{Buf, BufMut};
+
+///
+/// `NAWS` contains the Negotiated About Window Size payload.
+/// [RFC1073](http://www.iana.org/go/rfc1073)
+///
+/// A dimension of zero means the size is unknown.
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NAWS {
+    /// Width in characters
+    pub width: u16,
+    /// Height in characters
+    pub height: u16,
+}
+
+impl NAWS {
+    ///
+    /// Create a new `NAWS` payload
+    ///
+    pub fn new(width: u16, height: u16) -> NAWS {
+        NAWS { width, height }
+    }
+}
+
+impl Argument for NAWS {
+    fn len(&self) -> usize {
+        4
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        // A dimension containing 255 is escaped by the codec.
+        dst.put_u16(self.width);
+        dst.put_u16(self.height);
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        if src.remaining() != 4 {
+            return None;
+        }
+        Some(NAWS::new(src.get_u16(), src.get_u16()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NAWS;
+    use crate::codec::{Argument, TelnetCodec, TelnetFrame, TelnetOption};
+    use crate::consts;
+    use bytes::{Bytes, BytesMut};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    #[test]
+    fn naws_round_trip() {
+        let naws = NAWS::new(255, 80);
+        let mut arguments = Vec::new();
+        naws.encode(&mut arguments);
+        assert_eq!(arguments, vec![0, 255, 0, 80]);
+
+        let mut codec = TelnetCodec::default();
+        let mut encoded = BytesMut::new();
+        codec
+            .encode(
+                TelnetFrame::Subnegotiate(
+                    TelnetOption::NAWS,
+                    Bytes::from(arguments),
+                ),
+                &mut encoded,
+            )
+            .unwrap();
+        assert_eq!(
+            &encoded[..],
+            &[
+                consts::IAC,
+                consts::SB,
+                consts::option::NAWS,
+                0,
+                consts::IAC,
+                consts::IAC,
+                0,
+                80,
+                consts::IAC,
+                consts::SE,
+            ]
+        );
+        match codec.decode(&mut encoded).unwrap() {
+            Some(TelnetFrame::Subnegotiate(
+                TelnetOption::NAWS,
+                mut payload,
+            )) => {
+                assert_eq!(NAWS::decode(&mut payload), Some(naws));
+            }
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn naws_malformed() {
+        assert_eq!(NAWS::decode(&mut &[0u8, 80, 0][..]), None);
+        assert_eq!(NAWS::decode(&mut &[0u8, 80, 0, 24, 0][..]), None);
+    }
+}
@@ -0,0 +1,366 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::codec::Argument;
+use crate::consts;
+use bytes::{Buf, BufMut};
+use std::collections::BTreeMap;
+
+///
+/// Variables defined by the
+/// [MUD New-Environ Standard](https://tintin.mudhalla.net/protocols/mnes/),
+/// which clients report as well known variables.
+///
+pub const MNES: [&str; 6] = [
+    "CHARSET",
+    "CLIENT_NAME",
+    "CLIENT_VERSION",
+    "IPADDRESS",
+    "MTTS",
+    "TERMINAL_TYPE",
+];
+
+///
+/// Name of an environment variable.
+///
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum EnvironmentVariable {
+    /// Well known variable such as `USER` or `DISPLAY`
+    Var(String),
+    /// User defined variable
+    User(String),
+}
+
+impl EnvironmentVariable {
+    ///
+    /// Create a well known variable
+    ///
+    pub fn var(name: &str) -> EnvironmentVariable {
+        EnvironmentVariable::Var(name.to_string())
+    }
+    ///
+    /// Create a user defined variable
+    ///
+    pub fn user(name: &str) -> EnvironmentVariable {
+        EnvironmentVariable::User(name.to_string())
+    }
+    ///
+    /// Every variable defined by MNES.
+    ///
+    pub fn mnes() -> Vec<EnvironmentVariable> {
+        MNES.iter()
+            .map(|name| EnvironmentVariable::var(name))
+            .collect()
+    }
+    ///
+    /// Variable name
+    ///
+    pub fn name(&self) -> &str {
+        match self {
+            EnvironmentVariable::Var(name) => name,
+            EnvironmentVariable::User(name) => name,
+        }
+    }
+    ///
+    /// Whether `self` is requested by `request`, where an empty name
+    /// requests every variable of its kind.
+    ///
+    pub(crate) fn matches(&self, request: &EnvironmentVariable) -> bool {
+        match (self, request) {
+            (EnvironmentVariable::Var(_), EnvironmentVariable::Var(name))
+            | (EnvironmentVariable::User(_), EnvironmentVariable::User(name)) => {
+                name.is_empty() || name == self.name()
+            }
+            _ => false,
+        }
+    }
+}
+
+///
+/// Environment variables and their values, `None` for variables the remote
+/// reported as undefined.
+///
+pub type Environment = BTreeMap<EnvironmentVariable, Option<String>>;
+
+///
+/// `NewEnviron` contains the New Environment subnegotiation payload.
+/// [RFC1572](http://www.iana.org/go/rfc1572)
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NewEnviron {
+    /// Variables sent in answer to `Send`
+    Is(Environment),
+    /// Request for variables, where an empty name requests every variable
+    /// of its kind and an empty list requests everything
+    Send(Vec<EnvironmentVariable>),
+    /// Variables that changed since they were last sent
+    Info(Environment),
+}
+
+impl Argument for NewEnviron {
+    fn len(&self) -> usize {
+        Codes::NEW_ENVIRON.message_len(self)
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        Codes::NEW_ENVIRON.encode_message(self, dst)
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        if !src.has_remaining() {
+            return None;
+        }
+        let command = src.get_u8();
+        let tokens = tokenize(&src.to_bytes())?;
+        Codes::NEW_ENVIRON.decode_message(command, tokens)
+    }
+}
+
+///
+/// Type codes used to mark variables and values, which differ between
+/// implementations of the environment options.
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Codes {
+    pub(crate) var: u8,
+    pub(crate) value: u8,
+}
+
+impl Codes {
+    pub(crate) const NEW_ENVIRON: Codes = Codes {
+        var: consts::option::new_environ::VAR,
+        value: consts::option::new_environ::VALUE,
+    };
+    pub(crate) const SWAPPED: Codes = Codes {
+        var: consts::option::new_environ::VALUE,
+        value: consts::option::new_environ::VAR,
+    };
+
+    pub(crate) fn message_len(self, message: &NewEnviron) -> usize {
+        1 + match message {
+            NewEnviron::Is(environment) | NewEnviron::Info(environment) => {
+                self.environment_len(environment)
+            }
+            NewEnviron::Send(variables) => variables
+                .iter()
+                .map(|variable| 1 + self.escaped_len(variable.name()))
+                .sum(),
+        }
+    }
+    pub(crate) fn encode_message<T: BufMut>(
+        self,
+        message: &NewEnviron,
+        dst: &mut T,
+    ) {
+        match message {
+            NewEnviron::Is(environment) => {
+                dst.put_u8(consts::option::new_environ::IS);
+                self.encode_environment(environment, dst);
+            }
+            NewEnviron::Send(variables) => {
+                dst.put_u8(consts::option::new_environ::SEND);
+                for variable in variables {
+                    self.encode_variable(variable, dst);
+                }
+            }
+            NewEnviron::Info(environment) => {
+                dst.put_u8(consts::option::new_environ::INFO);
+                self.encode_environment(environment, dst);
+            }
+        }
+    }
+    pub(crate) fn decode_message(
+        self,
+        command: u8,
+        tokens: Vec<(u8, Vec<u8>)>,
+    ) -> Option<NewEnviron> {
+        match command {
+            consts::option::new_environ::IS => {
+                self.decode_environment(tokens).map(NewEnviron::Is)
+            }
+            consts::option::new_environ::SEND => {
+                self.decode_variables(tokens).map(NewEnviron::Send)
+            }
+            consts::option::new_environ::INFO => {
+                self.decode_environment(tokens).map(NewEnviron::Info)
+            }
+            _ => None,
+        }
+    }
+    fn escaped_len(self, text: &str) -> usize {
+        text.len() + text.bytes().filter(|byte| is_code(*byte)).count()
+    }
+    fn environment_len(self, environment: &Environment) -> usize {
+        environment
+            .iter()
+            .map(|(variable, value)| {
+                1 + self.escaped_len(variable.name())
+                    + value
+                        .as_ref()
+                        .map_or(0, |value| 1 + self.escaped_len(value))
+            })
+            .sum()
+    }
+    fn encode_escaped<T: BufMut>(self, text: &str, dst: &mut T) {
+        for byte in text.bytes() {
+            if is_code(byte) {
+                dst.put_u8(consts::option::new_environ::ESC);
+            }
+            dst.put_u8(byte);
+        }
+    }
+    pub(crate) fn encode_variable<T: BufMut>(
+        self,
+        variable: &EnvironmentVariable,
+        dst: &mut T,
+    ) {
+        dst.put_u8(match variable {
+            EnvironmentVariable::Var(_) => self.var,
+            EnvironmentVariable::User(_) => {
+                consts::option::new_environ::USERVAR
+            }
+        });
+        self.encode_escaped(variable.name(), dst);
+    }
+    pub(crate) fn encode_environment<T: BufMut>(
+        self,
+        environment: &Environment,
+        dst: &mut T,
+    ) {
+        for (variable, value) in environment {
+            self.encode_variable(variable, dst);
+            if let Some(value) = value {
+                dst.put_u8(self.value);
+                self.encode_escaped(value, dst);
+            }
+        }
+    }
+    fn variable(self, code: u8, name: Vec<u8>) -> Option<EnvironmentVariable> {
+        let name = String::from_utf8_lossy(&name).into_owned();
+        if code == self.var {
+            Some(EnvironmentVariable::Var(name))
+        } else if code == consts::option::new_environ::USERVAR {
+            Some(EnvironmentVariable::User(name))
+        } else {
+            None
+        }
+    }
+    fn decode_environment(
+        self,
+        tokens: Vec<(u8, Vec<u8>)>,
+    ) -> Option<Environment> {
+        let mut environment = Environment::new();
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some((code, name)) = tokens.next() {
+            let variable = self.variable(code, name)?;
+            let value = match tokens.peek() {
+                Some((code, _)) if *code == self.value => {
+                    tokens.next().map(|(_, value)| {
+                        String::from_utf8_lossy(&value).into_owned()
+                    })
+                }
+                _ => None,
+            };
+            environment.insert(variable, value);
+        }
+        Some(environment)
+    }
+    fn decode_variables(
+        self,
+        tokens: Vec<(u8, Vec<u8>)>,
+    ) -> Option<Vec<EnvironmentVariable>> {
+        tokens
+            .into_iter()
+            .map(|(code, name)| self.variable(code, name))
+            .collect()
+    }
+}
+
+///
+/// Whether `byte` must be escaped inside a name or value.
+///
+fn is_code(byte: u8) -> bool {
+    byte <= consts::option::new_environ::USERVAR
+}
+
+///
+/// Split an environment payload into type codes and the unescaped text
+/// following each of them.
+///
+pub(crate) fn tokenize(src: &[u8]) -> Option<Vec<(u8, Vec<u8>)>> {
+    let mut tokens: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut bytes = src.iter().copied();
+    while let Some(byte) = bytes.next() {
+        let byte = match byte {
+            consts::option::new_environ::ESC => bytes.next()?,
+            code if is_code(code) => {
+                tokens.push((code, Vec::new()));
+                continue;
+            }
+            byte => byte,
+        };
+        tokens.last_mut()?.1.push(byte);
+    }
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Environment, EnvironmentVariable, NewEnviron};
+    use crate::codec::Argument;
+
+    #[test]
+    fn new_environ_round_trip() {
+        let mut environment = Environment::new();
+        environment
+            .insert(EnvironmentVariable::var("USER"), Some("ann".into()));
+        environment.insert(EnvironmentVariable::var("DISPLAY"), None);
+        environment.insert(
+            EnvironmentVariable::user("ODD\x00\x02"),
+            Some("\x01".into()),
+        );
+        let messages = [
+            NewEnviron::Is(environment.clone()),
+            NewEnviron::Info(environment),
+            NewEnviron::Send(EnvironmentVariable::mnes()),
+            NewEnviron::Send(vec![]),
+        ];
+        for message in messages.iter() {
+            let mut arguments = Vec::new();
+            message.encode(&mut arguments);
+            assert_eq!(arguments.len(), message.len());
+            assert_eq!(
+                NewEnviron::decode(&mut &arguments[..]).as_ref(),
+                Some(message)
+            );
+        }
+    }
+
+    #[test]
+    fn new_environ_escaping() {
+        let mut arguments = Vec::new();
+        let mut environment = Environment::new();
+        environment
+            .insert(EnvironmentVariable::user("A\x03"), Some("\x02".into()));
+        NewEnviron::Is(environment).encode(&mut arguments);
+        assert_eq!(arguments, b"\x00\x03A\x02\x03\x01\x02\x02");
+
+        // Text before the first type code, or a dangling ESC, is malformed
+        assert_eq!(NewEnviron::decode(&mut &b"\x00USER"[..]), None);
+        assert_eq!(NewEnviron::decode(&mut &b"\x00\x00USER\x02"[..]), None);
+        // Values are not allowed in SEND
+        assert_eq!(NewEnviron::decode(&mut &b"\x01\x00USER\x01x"[..]), None);
+        assert_eq!(NewEnviron::decode(&mut &b"\x03"[..]), None);
+    }
+}
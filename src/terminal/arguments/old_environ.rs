@@ -0,0 +1,153 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::codec::Argument;
+use crate::terminal::option::new_environ::{tokenize, Codes, NewEnviron};
+use bytes::{Buf, BufMut};
+
+///
+/// `OldEnviron` contains the legacy Environment subnegotiation payload.
+/// [RFC1408](http://www.iana.org/go/rfc1408)
+///
+/// Many BSD derived implementations exchange the VAR and VALUE codes, so
+/// decoding follows the heuristics from
+/// [RFC1571](http://www.iana.org/go/rfc1571) and records which convention
+/// the remote used, allowing replies to be sent in the same convention.
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OldEnviron {
+    /// Message in the same form as NEW-ENVIRON
+    pub message: NewEnviron,
+    /// VAR and VALUE codes are exchanged
+    pub swapped: bool,
+}
+
+impl OldEnviron {
+    ///
+    /// Create a new `OldEnviron` payload
+    ///
+    pub fn new(message: NewEnviron, swapped: bool) -> OldEnviron {
+        OldEnviron { message, swapped }
+    }
+    ///
+    /// Decode a payload, trying the convention in `swapped` first. Payloads
+    /// that only make sense in the other convention are decoded in it.
+    ///
+    pub(crate) fn decode_preferring<T: Buf>(
+        src: &mut T,
+        swapped: bool,
+    ) -> Option<OldEnviron> {
+        if !src.has_remaining() {
+            return None;
+        }
+        let command = src.get_u8();
+        let tokens = tokenize(&src.to_bytes())?;
+        let decode = |swapped: bool| {
+            OldEnviron::codes_for(swapped)
+                .decode_message(command, tokens.clone())
+                .map(|message| OldEnviron::new(message, swapped))
+        };
+        decode(swapped).or_else(|| decode(!swapped))
+    }
+    fn codes(&self) -> Codes {
+        OldEnviron::codes_for(self.swapped)
+    }
+    fn codes_for(swapped: bool) -> Codes {
+        if swapped {
+            Codes::SWAPPED
+        } else {
+            Codes::NEW_ENVIRON
+        }
+    }
+}
+
+impl Argument for OldEnviron {
+    fn len(&self) -> usize {
+        self.codes().message_len(&self.message)
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        self.codes().encode_message(&self.message, dst)
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        OldEnviron::decode_preferring(src, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OldEnviron;
+    use crate::codec::Argument;
+    use crate::terminal::option::new_environ::{
+        Environment, EnvironmentVariable, NewEnviron,
+    };
+
+    #[test]
+    fn old_environ_swap_detection() {
+        let mut environment = Environment::new();
+        environment
+            .insert(EnvironmentVariable::var("USER"), Some("ann".into()));
+        environment.insert(EnvironmentVariable::var("DISPLAY"), None);
+        let message = NewEnviron::Is(environment);
+
+        let standard = OldEnviron::new(message.clone(), false);
+        let mut arguments = Vec::new();
+        standard.encode(&mut arguments);
+        assert_eq!(arguments, b"\x00\x00DISPLAY\x00USER\x01ann");
+        assert_eq!(OldEnviron::decode(&mut &arguments[..]), Some(standard));
+
+        let swapped = OldEnviron::new(message, true);
+        let mut arguments = Vec::new();
+        swapped.encode(&mut arguments);
+        assert_eq!(arguments.len(), swapped.len());
+        assert_eq!(arguments, b"\x00\x01DISPLAY\x01USER\x00ann");
+        assert_eq!(OldEnviron::decode(&mut &arguments[..]), Some(swapped));
+
+        // A BSD server asking for USER sends it with the VALUE code
+        assert_eq!(
+            OldEnviron::decode(&mut &b"\x01\x01USER"[..]),
+            Some(OldEnviron::new(
+                NewEnviron::Send(vec![EnvironmentVariable::var("USER")]),
+                true
+            ))
+        );
+        // Ambiguous payloads follow RFC1408
+        assert_eq!(
+            OldEnviron::decode(&mut &b"\x01\x00USER"[..]),
+            Some(OldEnviron::new(
+                NewEnviron::Send(vec![EnvironmentVariable::var("USER")]),
+                false
+            ))
+        );
+        // Once a remote is known to exchange the codes, ambiguous payloads
+        // are decoded that way
+        let ambiguous = b"\x00\x03ROOM\x00hall";
+        let mut environment = Environment::new();
+        environment.insert(EnvironmentVariable::user("ROOM"), None);
+        environment.insert(EnvironmentVariable::var("hall"), None);
+        assert_eq!(
+            OldEnviron::decode(&mut &ambiguous[..]),
+            Some(OldEnviron::new(NewEnviron::Is(environment), false))
+        );
+        let mut environment = Environment::new();
+        environment
+            .insert(EnvironmentVariable::user("ROOM"), Some("hall".into()));
+        assert_eq!(
+            OldEnviron::decode_preferring(&mut &ambiguous[..], true),
+            Some(OldEnviron::new(NewEnviron::Is(environment), true))
+        );
+        assert_eq!(OldEnviron::decode(&mut &b"\x00USER"[..]), None);
+    }
+}
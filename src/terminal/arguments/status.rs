@@ -14,37 +14,217 @@
 // limitations under the License.
 //
 
+use crate::codec::{Argument, TelnetOption};
 use crate::consts;
-use crate::terminal::options::TerminalOption;
-use crate::terminal::TerminalOption;
-use bytes::BufMut;
+use bytes::{Buf, BufMut};
 use std::collections::HashMap;
 
 ///
+/// Options a side of the connection reports as enabled, as (local, remote)
+/// from the point of view of the side sending the report.
 ///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TelnetOptionStatus(HashMap<TelnetOption, (bool, bool)>);
+
+impl TelnetOptionStatus {
+    ///
+    /// Create an empty `TelnetOptionStatus`, with every option disabled.
+    ///
+    pub fn new() -> TelnetOptionStatus {
+        TelnetOptionStatus::default()
+    }
+    ///
+    /// Whether the reporting side performs `option` itself.
+    ///
+    pub fn local(&self, option: TelnetOption) -> bool {
+        matches!(self.0.get(&option), Some((true, _)))
+    }
+    ///
+    /// Whether the reporting side has the other side perform `option`.
+    ///
+    pub fn remote(&self, option: TelnetOption) -> bool {
+        matches!(self.0.get(&option), Some((_, true)))
+    }
+    ///
+    /// Record whether `option` is enabled on each side.
+    ///
+    pub fn set(&mut self, option: TelnetOption, local: bool, remote: bool) {
+        if local || remote {
+            self.0.insert(option, (local, remote));
+        } else {
+            self.0.remove(&option);
+        }
+    }
+    ///
+    /// Options enabled on at least one side, in option code order.
+    ///
+    pub fn options(&self) -> Vec<TelnetOption> {
+        let mut options: Vec<TelnetOption> = self.0.keys().copied().collect();
+        options.sort_by_key(|option| u8::from(*option));
+        options
+    }
+    ///
+    /// The same status seen from the other side of the connection.
+    ///
+    pub fn reversed(&self) -> TelnetOptionStatus {
+        TelnetOptionStatus(
+            self.0
+                .iter()
+                .map(|(option, (local, remote))| (*option, (*remote, *local)))
+                .collect(),
+        )
+    }
+}
+
+///
+/// `Status` contains the STATUS subnegotiation payload.
+/// [RFC859](http://www.iana.org/go/rfc859)
+///
+/// SE bytes inside a report are doubled, as the RFC requires, on top of the
+/// IAC escaping performed by the codec.
 ///
 #[derive(Clone, Debug, PartialEq)]
-pub struct TelnetOptionStatus(HashMap<TerminalOption, (bool, bool)>);
+pub enum Status {
+    /// Report the options enabled by the sender
+    Is(TelnetOptionStatus),
+    /// Request a report
+    Send,
+}
 
-impl TelnetOptionStatus {
+impl Status {
     ///
-    /// Get Encoded Length of `TelnetOptionStatus`
+    /// Commands and options of a report, in the order they are sent.
     ///
-    pub(crate) fn len(&self) -> usize {
-        1 + self.0.len() * 4 // Two bytes per item
+    fn commands(status: &TelnetOptionStatus) -> Vec<(u8, u8)> {
+        let mut commands = Vec::new();
+        for option in status.options() {
+            if status.local(option) {
+                commands.push((consts::WILL, u8::from(option)));
+            }
+            if status.remote(option) {
+                commands.push((consts::DO, u8::from(option)));
+            }
+        }
+        commands
     }
     ///
-    /// Encode `TelnetOptionStatus` to `BufMut`
+    /// Read a byte of a report, collapsing a doubled SE.
     ///
-    pub(crate) fn encode<T: BufMut>(&self, dst: &mut T) {
-        /*
-        dst.put_u8(consts::negotiation::status::IS);
-        for (arguments, action) in &self.0 {
-            dst.put_u8(u8::from(action.0));
-            dst.put_u8(u8::from(*arguments));
-            dst.put_u8(u8::from(action.1));
-            dst.put_u8(u8::from(*arguments));
+    fn get_escaped<T: Buf>(src: &mut T) -> Option<u8> {
+        if !src.has_remaining() {
+            return None;
+        }
+        match src.get_u8() {
+            consts::SE
+                if src.has_remaining() && src.bytes()[0] == consts::SE =>
+            {
+                src.advance(1);
+                Some(consts::SE)
+            }
+            consts::SE => None,
+            byte => Some(byte),
+        }
+    }
+}
+
+impl Argument for Status {
+    fn len(&self) -> usize {
+        match self {
+            Status::Is(status) => {
+                Status::commands(status).iter().fold(1, |len, (_, option)| {
+                    len + if *option == consts::SE { 3 } else { 2 }
+                })
+            }
+            Status::Send => 1,
         }
-        */
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        match self {
+            Status::Is(status) => {
+                dst.put_u8(consts::option::status::IS);
+                for (command, option) in Status::commands(status) {
+                    dst.put_u8(command);
+                    dst.put_u8(option);
+                    if option == consts::SE {
+                        dst.put_u8(consts::SE);
+                    }
+                }
+            }
+            Status::Send => dst.put_u8(consts::option::status::SEND),
+        }
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        if !src.has_remaining() {
+            return None;
+        }
+        match src.get_u8() {
+            consts::option::status::IS => {
+                let mut status = TelnetOptionStatus::new();
+                while src.has_remaining() {
+                    let command = src.get_u8();
+                    let option = TelnetOption::from(Status::get_escaped(src)?);
+                    let (local, remote) =
+                        (status.local(option), status.remote(option));
+                    match command {
+                        consts::WILL => status.set(option, true, remote),
+                        consts::WONT => status.set(option, false, remote),
+                        consts::DO => status.set(option, local, true),
+                        consts::DONT => status.set(option, local, false),
+                        consts::SB => {
+                            // Subnegotiation state is not tracked, skip it
+                            // up to the single SE that ends it.
+                            while Status::get_escaped(src).is_some() {}
+                        }
+                        _ => return None,
+                    }
+                }
+                Some(Status::Is(status))
+            }
+            consts::option::status::SEND if !src.has_remaining() => {
+                Some(Status::Send)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Status, TelnetOptionStatus};
+    use crate::codec::{Argument, TelnetOption};
+
+    #[test]
+    fn status_round_trip() {
+        let mut status = TelnetOptionStatus::new();
+        status.set(TelnetOption::Echo, true, false);
+        status.set(TelnetOption::SuppressGoAhead, true, true);
+        status.set(TelnetOption::Unknown(240), false, true);
+        let mut arguments = Vec::new();
+        Status::Is(status.clone()).encode(&mut arguments);
+        assert_eq!(arguments, [0, 251, 1, 251, 3, 253, 3, 253, 240, 240]);
+        assert_eq!(arguments.len(), Status::Is(status.clone()).len());
+        assert_eq!(
+            Status::decode(&mut &arguments[..]),
+            Some(Status::Is(status))
+        );
+    }
+
+    #[test]
+    fn status_decode() {
+        // Subnegotiation state and refusals are accepted but not kept
+        let arguments = [
+            0, 253, 31, 250, 31, 0, 80, 240, 240, 0, 24, 240, 252, 1, 251, 1,
+            252, 1,
+        ];
+        let mut status = TelnetOptionStatus::new();
+        status.set(TelnetOption::NAWS, false, true);
+        assert_eq!(
+            Status::decode(&mut &arguments[..]),
+            Some(Status::Is(status.clone()))
+        );
+        assert!(status.reversed().local(TelnetOption::NAWS));
+        assert_eq!(Status::decode(&mut &[1][..]), Some(Status::Send));
+        assert_eq!(Status::decode(&mut &[0, 251][..]), None);
+        assert_eq!(Status::decode(&mut &[0, 1, 1][..]), None);
     }
 }
@@ -0,0 +1,250 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::codec::Argument;
+use crate::consts;
+use bytes::{Buf, BufMut};
+
+///
+/// `TTYPE` contains the Terminal Type subnegotiation payload.
+/// [RFC1091](http://www.iana.org/go/rfc1091)
+///
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TTYPE {
+    /// Report the next terminal type
+    Is(String),
+    /// Request the next terminal type
+    Send,
+}
+
+impl Argument for TTYPE {
+    fn len(&self) -> usize {
+        match self {
+            TTYPE::Is(name) => 1 + name.len(),
+            TTYPE::Send => 1,
+        }
+    }
+    fn encode<T: BufMut>(&self, dst: &mut T) {
+        match self {
+            TTYPE::Is(name) => {
+                dst.put_u8(consts::option::ttype::IS);
+                dst.put_slice(name.as_bytes());
+            }
+            TTYPE::Send => dst.put_u8(consts::option::ttype::SEND),
+        }
+    }
+    fn decode<T: Buf>(src: &mut T) -> Option<Self> {
+        if !src.has_remaining() {
+            return None;
+        }
+        match src.get_u8() {
+            consts::option::ttype::IS => {
+                // Names are restricted to ASCII, reject anything else.
+                let name = src.to_bytes();
+                if !name.is_ascii() {
+                    return None;
+                }
+                Some(TTYPE::Is(String::from_utf8_lossy(&name).into_owned()))
+            }
+            consts::option::ttype::SEND if !src.has_remaining() => {
+                Some(TTYPE::Send)
+            }
+            _ => None,
+        }
+    }
+}
+
+///
+/// Client capabilities advertised through the
+/// [MUD Terminal Type Standard](https://tintin.mudhalla.net/protocols/mtts/).
+///
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MTTS {
+    /// Supports ANSI color codes
+    pub ansi: bool,
+    /// Supports the VT100 interface
+    pub vt100: bool,
+    /// Uses UTF-8 character encoding
+    pub utf8: bool,
+    /// Supports 256 color codes
+    pub colors_256: bool,
+    /// Supports xterm mouse tracking
+    pub mouse_tracking: bool,
+    /// Supports the OSC color palette
+    pub osc_color_palette: bool,
+    /// Uses a screen reader
+    pub screen_reader: bool,
+    /// Is a proxy relaying another client
+    pub proxy: bool,
+    /// Supports 24 bit truecolor codes
+    pub truecolor: bool,
+    /// Supports the MUD New-Environ Standard
+    pub mnes: bool,
+    /// Supports the MUD Server Link Protocol
+    pub mslp: bool,
+    /// Connection is secured with TLS
+    pub ssl: bool,
+}
+
+impl MTTS {
+    const ANSI: u32 = 1;
+    const VT100: u32 = 2;
+    const UTF8: u32 = 4;
+    const COLORS_256: u32 = 8;
+    const MOUSE_TRACKING: u32 = 16;
+    const OSC_COLOR_PALETTE: u32 = 32;
+    const SCREEN_READER: u32 = 64;
+    const PROXY: u32 = 128;
+    const TRUECOLOR: u32 = 256;
+    const MNES: u32 = 512;
+    const MSLP: u32 = 1024;
+    const SSL: u32 = 2048;
+
+    ///
+    /// Decode the capabilities from an MTTS bitmask, unknown bits are
+    /// ignored.
+    ///
+    pub fn from_bits(bits: u32) -> MTTS {
+        MTTS {
+            ansi: bits & MTTS::ANSI != 0,
+            vt100: bits & MTTS::VT100 != 0,
+            utf8: bits & MTTS::UTF8 != 0,
+            colors_256: bits & MTTS::COLORS_256 != 0,
+            mouse_tracking: bits & MTTS::MOUSE_TRACKING != 0,
+            osc_color_palette: bits & MTTS::OSC_COLOR_PALETTE != 0,
+            screen_reader: bits & MTTS::SCREEN_READER != 0,
+            proxy: bits & MTTS::PROXY != 0,
+            truecolor: bits & MTTS::TRUECOLOR != 0,
+            mnes: bits & MTTS::MNES != 0,
+            mslp: bits & MTTS::MSLP != 0,
+            ssl: bits & MTTS::SSL != 0,
+        }
+    }
+    ///
+    /// Encode the capabilities as an MTTS bitmask.
+    ///
+    pub fn bits(&self) -> u32 {
+        let flags = [
+            (self.ansi, MTTS::ANSI),
+            (self.vt100, MTTS::VT100),
+            (self.utf8, MTTS::UTF8),
+            (self.colors_256, MTTS::COLORS_256),
+            (self.mouse_tracking, MTTS::MOUSE_TRACKING),
+            (self.osc_color_palette, MTTS::OSC_COLOR_PALETTE),
+            (self.screen_reader, MTTS::SCREEN_READER),
+            (self.proxy, MTTS::PROXY),
+            (self.truecolor, MTTS::TRUECOLOR),
+            (self.mnes, MTTS::MNES),
+            (self.mslp, MTTS::MSLP),
+            (self.ssl, MTTS::SSL),
+        ];
+        flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .fold(0, |bits, (_, flag)| bits | flag)
+    }
+    ///
+    /// Parse a terminal type of the form `MTTS <bitmask>`.
+    ///
+    pub fn parse(name: &str) -> Option<MTTS> {
+        let mut words = name.split_whitespace();
+        if !words.next()?.eq_ignore_ascii_case("MTTS") {
+            return None;
+        }
+        let bits = words.next()?.parse().ok()?;
+        match words.next() {
+            Some(_) => None,
+            None => Some(MTTS::from_bits(bits)),
+        }
+    }
+}
+
+///
+/// Terminal types reported by the remote, in the order they were sent.
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TerminalType {
+    /// Every distinct name reported by the remote
+    pub names: Vec<String>,
+    /// Capabilities from an `MTTS <bitmask>` entry, if one was reported
+    pub mtts: Option<MTTS>,
+}
+
+impl TerminalType {
+    ///
+    /// The remote's preferred terminal type.
+    ///
+    pub fn name(&self) -> Option<&str> {
+        self.names.first().map(String::as_str)
+    }
+    ///
+    /// Record the next name reported by the remote, returning `false` once
+    /// the remote starts repeating itself.
+    ///
+    pub(crate) fn push(&mut self, name: String) -> bool {
+        if self.names.contains(&name) {
+            return false;
+        }
+        if self.mtts.is_none() {
+            self.mtts = MTTS::parse(&name);
+        }
+        self.names.push(name);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TerminalType, MTTS, TTYPE};
+    use crate::codec::Argument;
+
+    #[test]
+    fn ttype_round_trip() {
+        let ttypes = [TTYPE::Send, TTYPE::Is("XTERM-256COLOR".to_string())];
+        for ttype in ttypes.iter() {
+            let mut arguments = Vec::new();
+            ttype.encode(&mut arguments);
+            assert_eq!(arguments.len(), ttype.len());
+            assert_eq!(
+                TTYPE::decode(&mut &arguments[..]).as_ref(),
+                Some(ttype)
+            );
+        }
+        assert_eq!(TTYPE::decode(&mut &b""[..]), None);
+        assert_eq!(TTYPE::decode(&mut &b"\x01x"[..]), None);
+        assert_eq!(TTYPE::decode(&mut &b"\x00caf\xC3\xA9"[..]), None);
+    }
+
+    #[test]
+    fn mtts_flags() {
+        let mtts = MTTS::parse("MTTS 2829").unwrap();
+        assert!(mtts.ansi && mtts.utf8 && mtts.colors_256);
+        assert!(mtts.truecolor && mtts.mnes && mtts.ssl);
+        assert!(!mtts.vt100 && !mtts.screen_reader && !mtts.proxy);
+        assert_eq!(mtts.bits(), 2829);
+        assert_eq!(MTTS::parse("MTTS"), None);
+        assert_eq!(MTTS::parse("MTTS x"), None);
+        assert_eq!(MTTS::parse("XTERM"), None);
+
+        let mut report = TerminalType::default();
+        assert!(report.push("MUDLET".to_string()));
+        assert!(report.push("XTERM".to_string()));
+        assert!(report.push("MTTS 137".to_string()));
+        assert!(!report.push("MTTS 137".to_string()));
+        assert_eq!(report.name(), Some("MUDLET"));
+        assert_eq!(report.mtts.map(|mtts| mtts.bits()), Some(137));
+    }
+}
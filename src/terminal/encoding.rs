@@ -0,0 +1,277 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::terminal::line;
+use crate::terminal::options::Utf8Replacement;
+use bytes::BytesMut;
+
+///
+/// Character encoding used for text exchanged with the remote.
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Encoding {
+    /// UTF-8
+    Utf8,
+    /// ISO-8859-1
+    Latin1,
+    /// US-ASCII
+    Ascii,
+    /// IBM PC code page 437, including its box drawing characters
+    Cp437,
+    /// KOI8-R Cyrillic
+    Koi8R,
+}
+
+impl Encoding {
+    ///
+    /// Look up an encoding by its IANA name or a common alias, ignoring
+    /// case.
+    ///
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        let name = name.trim().to_ascii_uppercase();
+        match name.as_str() {
+            "UTF-8" | "UTF8" => Some(Encoding::Utf8),
+            "ISO-8859-1" | "ISO_8859-1" | "ISO8859-1" | "LATIN1"
+            | "LATIN-1" | "L1" | "CP819" | "IBM819" => Some(Encoding::Latin1),
+            "US-ASCII" | "ASCII" | "ANSI_X3.4-1968" | "US" => {
+                Some(Encoding::Ascii)
+            }
+            "IBM437" | "CP437" | "437" | "CSPC8CODEPAGE437" => {
+                Some(Encoding::Cp437)
+            }
+            "KOI8-R" | "KOI8R" | "CSKOI8R" => Some(Encoding::Koi8R),
+            _ => None,
+        }
+    }
+    ///
+    /// IANA name of the encoding.
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Ascii => "US-ASCII",
+            Encoding::Cp437 => "IBM437",
+            Encoding::Koi8R => "KOI8-R",
+        }
+    }
+    ///
+    /// Encode `text`, replacing characters the encoding cannot represent
+    /// with `?`.
+    ///
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Latin1 => text
+                .chars()
+                .map(|ch| {
+                    if u32::from(ch) < 0x100 {
+                        ch as u8
+                    } else {
+                        b'?'
+                    }
+                })
+                .collect(),
+            Encoding::Ascii => text
+                .chars()
+                .map(|ch| if ch.is_ascii() { ch as u8 } else { b'?' })
+                .collect(),
+            Encoding::Cp437 => encode_code_page(text, &CP437),
+            Encoding::Koi8R => encode_code_page(text, &KOI8_R),
+        }
+    }
+    ///
+    /// Decode the text in `buffer`, leaving behind a trailing incomplete
+    /// sequence unless `last` is set.
+    ///
+    pub(crate) fn decode(
+        &self,
+        buffer: &mut BytesMut,
+        replacement: Utf8Replacement,
+        last: bool,
+    ) -> String {
+        let text = match self {
+            Encoding::Utf8 => {
+                return line::decode_utf8(buffer, replacement, last);
+            }
+            Encoding::Latin1 => buffer.iter().map(|&b| char::from(b)).collect(),
+            Encoding::Ascii => buffer
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii() {
+                        char::from(b)
+                    } else {
+                        char::REPLACEMENT_CHARACTER
+                    }
+                })
+                .collect(),
+            Encoding::Cp437 => decode_code_page(buffer, &CP437),
+            Encoding::Koi8R => decode_code_page(buffer, &KOI8_R),
+        };
+        buffer.clear();
+        text
+    }
+}
+
+///
+/// Encode `text` in a single byte code page whose upper half is `upper`.
+///
+fn encode_code_page(text: &str, upper: &[char; 128]) -> Vec<u8> {
+    text.chars()
+        .map(|ch| {
+            if ch.is_ascii() {
+                return ch as u8;
+            }
+            match upper.iter().position(|&c| c == ch) {
+                Some(index) => 0x80 + index as u8,
+                None => b'?',
+            }
+        })
+        .collect()
+}
+
+///
+/// Decode `data` from a single byte code page whose upper half is `upper`.
+///
+fn decode_code_page(data: &[u8], upper: &[char; 128]) -> String {
+    data.iter()
+        .map(|&b| {
+            if b.is_ascii() {
+                char::from(b)
+            } else {
+                upper[usize::from(b - 0x80)]
+            }
+        })
+        .collect()
+}
+
+///
+/// Code page 437 characters for bytes `0x80..=0xFF`.
+///
+const CP437: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}',
+    '\u{00E5}', '\u{00E7}', '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}',
+    '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}', '\u{00C9}', '\u{00E6}',
+    '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00A2}', '\u{00A3}', '\u{00A5}',
+    '\u{20A7}', '\u{0192}', '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}',
+    '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}', '\u{00BF}', '\u{2310}',
+    '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}',
+    '\u{2562}', '\u{2556}', '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}',
+    '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}', '\u{2514}', '\u{2534}',
+    '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}',
+    '\u{256C}', '\u{2567}', '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}',
+    '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}', '\u{256A}', '\u{2518}',
+    '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}',
+    '\u{00B5}', '\u{03C4}', '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}',
+    '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}', '\u{2261}', '\u{00B1}',
+    '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}',
+    '\u{25A0}', '\u{00A0}',
+];
+
+///
+/// KOI8-R characters for bytes `0x80..=0xFF`.
+///
+const KOI8_R: [char; 128] = [
+    '\u{2500}', '\u{2502}', '\u{250C}', '\u{2510}', '\u{2514}', '\u{2518}',
+    '\u{251C}', '\u{2524}', '\u{252C}', '\u{2534}', '\u{253C}', '\u{2580}',
+    '\u{2584}', '\u{2588}', '\u{258C}', '\u{2590}', '\u{2591}', '\u{2592}',
+    '\u{2593}', '\u{2320}', '\u{25A0}', '\u{2219}', '\u{221A}', '\u{2248}',
+    '\u{2264}', '\u{2265}', '\u{00A0}', '\u{2321}', '\u{00B0}', '\u{00B2}',
+    '\u{00B7}', '\u{00F7}', '\u{2550}', '\u{2551}', '\u{2552}', '\u{0451}',
+    '\u{2553}', '\u{2554}', '\u{2555}', '\u{2556}', '\u{2557}', '\u{2558}',
+    '\u{2559}', '\u{255A}', '\u{255B}', '\u{255C}', '\u{255D}', '\u{255E}',
+    '\u{255F}', '\u{2560}', '\u{2561}', '\u{0401}', '\u{2562}', '\u{2563}',
+    '\u{2564}', '\u{2565}', '\u{2566}', '\u{2567}', '\u{2568}', '\u{2569}',
+    '\u{256A}', '\u{256B}', '\u{256C}', '\u{00A9}', '\u{044E}', '\u{0430}',
+    '\u{0431}', '\u{0446}', '\u{0434}', '\u{0435}', '\u{0444}', '\u{0433}',
+    '\u{0445}', '\u{0438}', '\u{0439}', '\u{043A}', '\u{043B}', '\u{043C}',
+    '\u{043D}', '\u{043E}', '\u{043F}', '\u{044F}', '\u{0440}', '\u{0441}',
+    '\u{0442}', '\u{0443}', '\u{0436}', '\u{0432}', '\u{044C}', '\u{044B}',
+    '\u{0437}', '\u{0448}', '\u{044D}', '\u{0449}', '\u{0447}', '\u{044A}',
+    '\u{042E}', '\u{0410}', '\u{0411}', '\u{0426}', '\u{0414}', '\u{0415}',
+    '\u{0424}', '\u{0413}', '\u{0425}', '\u{0418}', '\u{0419}', '\u{041A}',
+    '\u{041B}', '\u{041C}', '\u{041D}', '\u{041E}', '\u{041F}', '\u{042F}',
+    '\u{0420}', '\u{0421}', '\u{0422}', '\u{0423}', '\u{0416}', '\u{0412}',
+    '\u{042C}', '\u{042B}', '\u{0417}', '\u{0428}', '\u{042D}', '\u{0429}',
+    '\u{0427}', '\u{042A}',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::Encoding;
+    use crate::terminal::options::Utf8Replacement;
+    use bytes::BytesMut;
+
+    #[test]
+    fn encoding_names() {
+        let encodings = [
+            Encoding::Utf8,
+            Encoding::Latin1,
+            Encoding::Ascii,
+            Encoding::Cp437,
+            Encoding::Koi8R,
+        ];
+        for encoding in &encodings {
+            assert_eq!(Encoding::from_name(encoding.name()), Some(*encoding));
+        }
+        assert_eq!(Encoding::from_name("latin1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_name("EBCDIC"), None);
+    }
+
+    #[test]
+    fn latin1_round_trip() {
+        let encoding = Encoding::Latin1;
+        assert_eq!(encoding.encode("caf\u{e9} \u{20ac}"), b"caf\xE9 ?");
+        let mut buffer = BytesMut::from(&b"caf\xE9"[..]);
+        let replacement = Utf8Replacement::Substitute;
+        assert_eq!(
+            encoding.decode(&mut buffer, replacement, false),
+            "caf\u{e9}"
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn code_pages() {
+        let replacement = Utf8Replacement::Substitute;
+        let decode = |encoding: Encoding, bytes: &[u8]| {
+            encoding.decode(&mut BytesMut::from(bytes), replacement, false)
+        };
+        // Box drawing, shading and line characters from a BBS menu
+        let menu = "\u{2554}\u{2550}\u{2557} \u{2591}\u{2593} \u{00E9}";
+        let cp437 = b"\xC9\xCD\xBB \xB0\xB2 \x82";
+        assert_eq!(Encoding::Cp437.encode(menu), cp437);
+        assert_eq!(decode(Encoding::Cp437, cp437), menu);
+
+        let greeting = "\u{041F}\u{0440}\u{0438}\u{0432}\u{0435}\u{0442}!";
+        let koi8_r = b"\xF0\xD2\xC9\xD7\xC5\xD4!";
+        assert_eq!(Encoding::Koi8R.encode(greeting), koi8_r);
+        assert_eq!(decode(Encoding::Koi8R, koi8_r), greeting);
+
+        // Every byte survives a round trip
+        let bytes: Vec<u8> = (0..=255).collect();
+        for encoding in &[Encoding::Cp437, Encoding::Koi8R, Encoding::Latin1] {
+            let text = decode(*encoding, &bytes);
+            assert_eq!(encoding.encode(&text), bytes);
+        }
+        assert_eq!(Encoding::Cp437.encode("\u{20AC}"), b"?");
+    }
+}
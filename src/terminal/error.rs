@@ -14,27 +14,67 @@
 // limitations under the License.
 //
 
+use crate::codec::{DecodeError, EncodeError};
 use std::{error, fmt};
 
 ///
 /// Telnet Network Virtual Terminal Error
 ///
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum TerminalError {
     /// Error Configuring Terminal
     ConfigurationError,
     /// Error Negotiating With Remote
     NegotationError,
+    /// Remote did not answer in time
+    Timeout,
+    /// Remote closed the connection
+    Closed,
+    /// Error Decoding Incoming Stream
+    DecodeError(DecodeError),
+    /// Error Encoding Outgoing Stream
+    EncodeError(EncodeError),
 }
 
 impl error::Error for TerminalError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        None
+        match self {
+            TerminalError::DecodeError(inner) => Some(inner),
+            TerminalError::EncodeError(inner) => Some(inner),
+            _ => None,
+        }
     }
 }
 
 impl fmt::Display for TerminalError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "TerminalError")
+        match self {
+            TerminalError::ConfigurationError => {
+                write!(f, "TerminalError::ConfigurationError")
+            }
+            TerminalError::NegotationError => {
+                write!(f, "TerminalError::NegotationError")
+            }
+            TerminalError::Timeout => write!(f, "TerminalError::Timeout"),
+            TerminalError::Closed => write!(f, "TerminalError::Closed"),
+            TerminalError::DecodeError(inner) => {
+                write!(f, "TerminalError::DecodeError({})", inner)
+            }
+            TerminalError::EncodeError(inner) => {
+                write!(f, "TerminalError::EncodeError({})", inner)
+            }
+        }
+    }
+}
+
+impl From<DecodeError> for TerminalError {
+    fn from(error: DecodeError) -> TerminalError {
+        TerminalError::DecodeError(error)
+    }
+}
+
+impl From<EncodeError> for TerminalError {
+    fn from(error: EncodeError) -> TerminalError {
+        TerminalError::EncodeError(error)
     }
 }
@@ -0,0 +1,162 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::codec::{Argument, TelnetFrame, TelnetOption};
+use crate::terminal::{TerminalEndpoint, TerminalOutput};
+
+use bytes::Bytes;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+///
+/// Handler for a single `TelnetOption` registered with a
+/// `NetworkVirtualTerminal`.
+///
+/// Handlers let applications implement custom or proprietary options without
+/// modifying the terminal. Whether an option is accepted at all is still
+/// decided by the `TerminalConfiguration`.
+///
+/// Handlers for options the terminal implements itself only receive the
+/// `enabled` and `disabled` callbacks.
+///
+pub trait OptionHandler: Send {
+    ///
+    /// Option was enabled on `endpoint`.
+    ///
+    fn enabled(
+        &mut self,
+        _endpoint: TerminalEndpoint,
+        _context: &mut HandlerContext,
+    ) {
+    }
+    ///
+    /// Option was disabled on `endpoint`.
+    ///
+    fn disabled(
+        &mut self,
+        _endpoint: TerminalEndpoint,
+        _context: &mut HandlerContext,
+    ) {
+    }
+    ///
+    /// Remote sent `IAC SB option arguments IAC SE`.
+    ///
+    /// Never called for options the terminal implements itself, see
+    /// `NetworkVirtualTerminal::register`.
+    ///
+    fn subnegotiation(
+        &mut self,
+        _arguments: &[u8],
+        _context: &mut HandlerContext,
+    ) {
+    }
+}
+
+///
+/// Actions an `OptionHandler` may take from inside a callback.
+///
+pub struct HandlerContext {
+    option: TelnetOption,
+    pub(crate) frames: Vec<TelnetFrame>,
+    pub(crate) outputs: Vec<TerminalOutput>,
+}
+
+impl HandlerContext {
+    pub(crate) fn new(option: TelnetOption) -> HandlerContext {
+        HandlerContext {
+            option,
+            frames: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+    ///
+    /// Option this handler is registered for.
+    ///
+    pub fn option(&self) -> TelnetOption {
+        self.option
+    }
+    ///
+    /// Send a subnegotiation for this option to the remote.
+    ///
+    pub fn send<B: Into<Bytes>>(&mut self, arguments: B) {
+        self.frames
+            .push(TelnetFrame::Subnegotiate(self.option, arguments.into()));
+    }
+    ///
+    /// Encode `argument` and send it as a subnegotiation for this option.
+    ///
+    pub fn send_argument<A: Argument>(&mut self, argument: &A) {
+        let mut arguments = Vec::with_capacity(argument.len());
+        argument.encode(&mut arguments);
+        self.send(arguments);
+    }
+    ///
+    /// Emit `event` from the terminal `Stream` as
+    /// `TerminalOutput::OptionEvent`.
+    ///
+    pub fn emit<T>(&mut self, event: T)
+    where
+        T: Any + fmt::Debug + Send + Sync,
+    {
+        self.outputs.push(TerminalOutput::OptionEvent(
+            self.option,
+            OptionEvent(Arc::new(event)),
+        ));
+    }
+}
+
+///
+/// Typed event emitted by an `OptionHandler`.
+///
+#[derive(Clone)]
+pub struct OptionEvent(Arc<dyn Event>);
+
+impl OptionEvent {
+    ///
+    /// Get the event if it is of type `T`.
+    ///
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        // Deref first, `Arc<dyn Event>` is itself an `Event`.
+        Event::as_any(&*self.0).downcast_ref::<T>()
+    }
+}
+
+impl fmt::Debug for OptionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OptionEvent").field(&self.0).finish()
+    }
+}
+
+/// Events are only equal when they are the same emitted event.
+impl PartialEq for OptionEvent {
+    fn eq(&self, other: &OptionEvent) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+trait Event: fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> Event for T
+where
+    T: Any + fmt::Debug + Send + Sync,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
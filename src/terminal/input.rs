@@ -14,11 +14,9 @@
 // limitations under the License.
 //
 
-use crate::terminal::arguments::{
-    TelnetArgument, TelnetOption, TelnetOptionArguments,
-};
-use crate::terminal::options::TerminalOption;
-use crate::terminal::{OptionStatus, TerminalEndpoint};
+use crate::codec::TelnetOption;
+
+use bytes::Bytes;
 
 ///
 /// Valid Input Events to the Terminal
@@ -43,12 +41,26 @@ pub enum TerminalInput {
     EraseLine,
     /// End of input for half-duplex connections.
     GoAhead,
+    /// End of File.
+    EndOfFile,
+    /// Suspend the current process.
+    Suspend,
+    /// Abort the current process.
+    Abort,
+    /// End of Record, commonly used to mark prompts.
+    EndOfRecord,
     /// Terminal Received Line of Ascii
     AsciiData(String),
     /// Terminal Received Binary Data
     BinaryData(Vec<u8>),
-    /// Set Option Status
-    TerminalOptionStatus(TerminalEndpoint, TerminalOption, OptionStatus),
-    /// Send Argument
-    TerminalOptionArgument(TerminalEndpoint, TerminalOptionArgument),
+    /// Ask to start performing an option locally
+    EnableLocalOption(TelnetOption),
+    /// Ask to stop performing an option locally
+    DisableLocalOption(TelnetOption),
+    /// Ask the remote to start performing an option
+    EnableRemoteOption(TelnetOption),
+    /// Ask the remote to stop performing an option
+    DisableRemoteOption(TelnetOption),
+    /// Send a subnegotiation for an option
+    Subnegotiation(TelnetOption, Bytes),
 }
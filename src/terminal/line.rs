@@ -0,0 +1,164 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::consts;
+use crate::terminal::encoding::Encoding;
+use crate::terminal::options::{EightBitPolicy, Utf8Replacement};
+use bytes::{Buf, BytesMut};
+use std::str;
+
+///
+/// Take the next complete line, including its `\n`, from `buffer`.
+///
+pub(crate) fn take_line(
+    buffer: &mut BytesMut,
+    charset: Option<Encoding>,
+    policy: EightBitPolicy,
+    replacement: Utf8Replacement,
+) -> Option<String> {
+    let end = buffer.iter().position(|&b| b == consts::LF)?;
+    let mut line = buffer.split_to(end + 1);
+    Some(take_text(&mut line, charset, policy, replacement, true))
+}
+
+///
+/// Take the text in `buffer`, leaving behind a trailing incomplete UTF-8
+/// sequence unless `last` is set. Text in an agreed `charset` is decoded
+/// from it, otherwise `policy` decides what to do with 8-bit data.
+///
+pub(crate) fn take_text(
+    buffer: &mut BytesMut,
+    charset: Option<Encoding>,
+    policy: EightBitPolicy,
+    replacement: Utf8Replacement,
+    last: bool,
+) -> String {
+    if let Some(charset) = charset {
+        return charset.decode(buffer, replacement, last);
+    }
+    match policy {
+        EightBitPolicy::Strip => {
+            let text = buffer.iter().map(|&b| char::from(b & 0x7F)).collect();
+            buffer.clear();
+            text
+        }
+        EightBitPolicy::PassThrough => {
+            let text = buffer.iter().map(|&b| char::from(b)).collect();
+            buffer.clear();
+            text
+        }
+        EightBitPolicy::Utf8 => decode_utf8(buffer, replacement, last),
+    }
+}
+
+///
+/// Take the UTF-8 text in `buffer`, leaving behind a trailing incomplete
+/// sequence unless `last` is set.
+///
+pub(crate) fn decode_utf8(
+    buffer: &mut BytesMut,
+    replacement: Utf8Replacement,
+    last: bool,
+) -> String {
+    let mut text = String::with_capacity(buffer.len());
+    while !buffer.is_empty() {
+        let error = match str::from_utf8(buffer) {
+            Ok(valid) => {
+                text.push_str(valid);
+                buffer.clear();
+                break;
+            }
+            Err(error) => error,
+        };
+        let valid = error.valid_up_to();
+        // Safe to unwrap, the prefix was just validated.
+        text.push_str(str::from_utf8(&buffer[..valid]).unwrap());
+        buffer.advance(valid);
+        let invalid = match error.error_len() {
+            Some(invalid) => invalid,
+            // Rest of the sequence is still to come.
+            None if !last => break,
+            None => buffer.len(),
+        };
+        replace(&buffer[..invalid], replacement, &mut text);
+        buffer.advance(invalid);
+    }
+    text
+}
+
+///
+/// Append the replacement for the `invalid` UTF-8 bytes to `text`.
+///
+fn replace(invalid: &[u8], replacement: Utf8Replacement, text: &mut String) {
+    match replacement {
+        Utf8Replacement::Substitute => text.push(char::REPLACEMENT_CHARACTER),
+        Utf8Replacement::Latin1 => {
+            text.extend(invalid.iter().map(|&b| char::from(b)))
+        }
+        Utf8Replacement::Discard => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{take_line, take_text};
+    use crate::terminal::options::{EightBitPolicy, Utf8Replacement};
+    use bytes::BytesMut;
+
+    #[test]
+    fn utf8_across_reads() {
+        let policy = EightBitPolicy::Utf8;
+        let replacement = Utf8Replacement::Substitute;
+        let mut buffer = BytesMut::from(&b"caf\xC3"[..]);
+        assert_eq!(take_line(&mut buffer, None, policy, replacement), None);
+        assert_eq!(
+            take_text(&mut buffer, None, policy, replacement, false),
+            "caf"
+        );
+        assert_eq!(&buffer[..], b"\xC3");
+        buffer.extend_from_slice(b"\xA9\nmore");
+        assert_eq!(
+            take_line(&mut buffer, None, policy, replacement),
+            Some("\u{e9}\n".to_string())
+        );
+        assert_eq!(&buffer[..], b"more");
+    }
+
+    #[test]
+    fn utf8_replacement() {
+        let policy = EightBitPolicy::Utf8;
+        let input = &b"a\xFFb\xC3\n"[..];
+        let line = |replacement| {
+            take_line(&mut BytesMut::from(input), None, policy, replacement)
+        };
+        assert_eq!(
+            line(Utf8Replacement::Substitute),
+            Some("a\u{fffd}b\u{fffd}\n".to_string())
+        );
+        assert_eq!(
+            line(Utf8Replacement::Latin1),
+            Some("a\u{ff}b\u{c3}\n".to_string())
+        );
+        assert_eq!(line(Utf8Replacement::Discard), Some("ab\n".to_string()));
+        // Incomplete sequence at the very end of the input
+        let mut buffer = BytesMut::from(&b"a\xC3"[..]);
+        assert_eq!(
+            take_text(&mut buffer, None, policy, Utf8Replacement::Latin1, true),
+            "a\u{c3}"
+        );
+        assert!(buffer.is_empty());
+    }
+}
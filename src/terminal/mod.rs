@@ -15,24 +15,43 @@
 //
 
 mod arguments;
+mod encoding;
 mod error;
+mod handler;
 mod input;
+mod line;
+mod nvt;
 mod options;
 mod output;
 mod state;
+#[allow(clippy::module_inception)]
 mod terminal;
 
+pub use self::encoding::Encoding;
 pub use self::error::TerminalError;
+pub use self::handler::{HandlerContext, OptionEvent, OptionHandler};
 pub use self::input::TerminalInput;
+pub use self::options::{
+    Compatibility, EightBitPolicy, InputMode, OptionStatus, TerminalEndpoint,
+    Utf8Replacement,
+};
 pub use self::output::TerminalOutput;
+pub use self::state::{TerminalConfiguration, TerminalConfigurationBuilder};
 pub use self::terminal::NetworkVirtualTerminal;
 
 pub mod option {
+    pub use super::arguments::charset;
+    pub use super::arguments::linemode;
     pub use super::arguments::msdp;
     pub use super::arguments::mssp;
     pub use super::arguments::naocrd;
     pub use super::arguments::naohts;
+    pub use super::arguments::naws;
+    pub use super::arguments::new_environ;
+    pub use super::arguments::old_environ;
     pub use super::arguments::status;
-    pub use super::arguments::TelnetArgument;
-    pub use super::options::TerminalOption;
+    pub use super::arguments::ttype;
+    pub use super::options::{
+        TerminalAction, TerminalOption, TerminalOptionState,
+    };
 }
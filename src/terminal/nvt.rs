@@ -0,0 +1,111 @@
+//
+// Copyright 2019 Hans W. Uhlig. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::consts;
+use bytes::BytesMut;
+
+///
+/// Write `data` to `dst` as NVT ASCII, sending newlines as CR LF and any
+/// other CR as CR NUL as required by
+/// [RFC854](https://tools.ietf.org/html/rfc854).
+///
+pub(crate) fn encode_newlines(data: &[u8], dst: &mut BytesMut) {
+    dst.reserve(data.len());
+    let mut bytes = data.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            consts::CR if bytes.peek() == Some(&consts::LF) => {
+                bytes.next();
+                dst.extend_from_slice(&[consts::CR, consts::LF]);
+            }
+            consts::CR => dst.extend_from_slice(&[consts::CR, consts::NUL]),
+            consts::LF => dst.extend_from_slice(&[consts::CR, consts::LF]),
+            _ => dst.extend_from_slice(&[byte]),
+        }
+    }
+}
+
+///
+/// Translates NVT ASCII received from the remote, turning CR LF, CR NUL and
+/// bare LF into a single `\n`.
+///
+/// A CR at the end of one run of data is held until the next byte arrives.
+///
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NewlineDecoder {
+    carriage_return: bool,
+}
+
+impl NewlineDecoder {
+    ///
+    /// Translate `data`, appending the result to `dst`.
+    ///
+    pub(crate) fn decode(&mut self, data: &[u8], dst: &mut BytesMut) {
+        dst.reserve(data.len() + 1);
+        for &byte in data {
+            if self.carriage_return {
+                self.carriage_return = false;
+                match byte {
+                    consts::LF | consts::NUL => {
+                        dst.extend_from_slice(&[consts::LF]);
+                        continue;
+                    }
+                    _ => dst.extend_from_slice(&[consts::CR]),
+                }
+            }
+            if byte == consts::CR {
+                self.carriage_return = true;
+            } else {
+                dst.extend_from_slice(&[byte]);
+            }
+        }
+    }
+    ///
+    /// Write out a CR still waiting for the byte after it.
+    ///
+    pub(crate) fn flush(&mut self, dst: &mut BytesMut) {
+        if self.carriage_return {
+            self.carriage_return = false;
+            dst.extend_from_slice(&[consts::CR]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_newlines, NewlineDecoder};
+    use bytes::BytesMut;
+
+    #[test]
+    fn encode() {
+        let mut dst = BytesMut::new();
+        encode_newlines(b"one\ntwo\r\nthree\rfour\r", &mut dst);
+        assert_eq!(&dst[..], &b"one\r\ntwo\r\nthree\r\0four\r\0"[..]);
+    }
+
+    #[test]
+    fn decode() {
+        let mut decoder = NewlineDecoder::default();
+        let mut dst = BytesMut::new();
+        decoder.decode(b"one\r\ntwo\r\0three\nfour\rfive\r", &mut dst);
+        assert_eq!(&dst[..], &b"one\ntwo\nthree\nfour\rfive"[..]);
+        // CR LF split across two runs
+        decoder.decode(b"\nsix\r", &mut dst);
+        assert_eq!(&dst[..], &b"one\ntwo\nthree\nfour\rfive\nsix"[..]);
+        decoder.flush(&mut dst);
+        assert_eq!(&dst[..], &b"one\ntwo\nthree\nfour\rfive\nsix\r"[..]);
+    }
+}
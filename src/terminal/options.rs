@@ -15,12 +15,11 @@
 //
 
 use crate::codec::TelnetOption;
-use crate::consts;
-use crate::terminal::TerminalEndpoint;
 
 ///
 /// Which Side of the Terminal
 ///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum TerminalEndpoint {
     /// Local Terminal Side
     Local,
@@ -31,6 +30,7 @@ pub enum TerminalEndpoint {
 ///
 /// Terminal Actions
 ///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum TerminalAction {
     /// Sending Data
     Sending,
@@ -39,17 +39,19 @@ pub enum TerminalAction {
 }
 
 ///
-/// State a `TerminalOption` can exist in.
+/// Policy a `TerminalOption` is configured with.
 ///
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub enum TerminalOptionState {
-    /// Option is unsupported by this library
+    /// Option is never negotiated, even when requested locally.
     Unsupported,
-    /// Option is supported by the library but disallowed by the user.
+    /// Option may be requested locally but is refused when the remote asks.
+    #[default]
     Supported,
-    /// Option is allowed by the user but not yet negotiated
+    /// Option is accepted when the remote asks.
     Allowed,
-    /// Option is negotiated
-    Enabled,
+    /// Option is accepted and requested as soon as the terminal connects.
+    RequestOnConnect,
 }
 
 ///
@@ -76,8 +78,82 @@ impl TerminalOption {
     pub fn option(&self) -> TelnetOption {
         match *self {
             TerminalOption::SendBinary => TelnetOption::TransmitBinary,
-            TerminalOption::ReceiveBinary => TerminalEndpoint::Remote,
+            TerminalOption::ReceiveBinary => TelnetOption::TransmitBinary,
             TerminalOption::Unknown(_, option) => option,
         }
     }
 }
+
+///
+/// Negotiated Status of an Option
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OptionStatus {
+    /// Option has been agreed to by both sides
+    Enabled,
+    /// Option is off
+    Disabled,
+}
+
+impl From<bool> for OptionStatus {
+    fn from(enabled: bool) -> Self {
+        if enabled {
+            OptionStatus::Enabled
+        } else {
+            OptionStatus::Disabled
+        }
+    }
+}
+
+///
+/// How input from the remote is delivered.
+///
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum InputMode {
+    /// A line at a time, the remote echoes and edits locally.
+    #[default]
+    Line,
+    /// A character at a time as soon as it arrives, echoed by this side.
+    Character,
+}
+
+///
+/// Handling of bytes with the high bit set outside of binary mode.
+///
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum EightBitPolicy {
+    /// Clear the high bit, as NVT ASCII requires.
+    #[default]
+    Strip,
+    /// Keep every byte, received bytes are read as Latin-1.
+    PassThrough,
+    /// Keep every byte, received bytes are read as UTF-8.
+    Utf8,
+}
+
+///
+/// Handling of 8-bit data in each direction while TRANSMIT-BINARY is off.
+///
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Compatibility {
+    /// Policy for data sent to the remote
+    pub send_while_normal: EightBitPolicy,
+    /// Policy for data received from the remote
+    pub recv_while_normal: EightBitPolicy,
+    /// Replacement for invalid UTF-8 received under `EightBitPolicy::Utf8`
+    pub invalid_utf8: Utf8Replacement,
+}
+
+///
+/// Replacement for bytes that are not valid UTF-8.
+///
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Utf8Replacement {
+    /// Replace each invalid sequence with U+FFFD.
+    #[default]
+    Substitute,
+    /// Read each invalid byte as Latin-1.
+    Latin1,
+    /// Drop invalid bytes.
+    Discard,
+}
@@ -14,13 +14,17 @@
 // limitations under the License.
 //
 
-use super::option::TerminalOption;
-use crate::terminal::{OptionStatus, TerminalEndpoint};
+use crate::codec::TelnetOption;
+use crate::terminal::option::linemode::{LinemodeMode, SlcTriplet};
+use crate::terminal::option::new_environ::Environment;
+use crate::terminal::option::status::TelnetOptionStatus;
+use crate::terminal::option::ttype::TerminalType;
+use crate::terminal::{Encoding, OptionEvent, OptionStatus, TerminalEndpoint};
 
 ///
 /// Output generated from the Telnet Network Virtual Terminal
 ///
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum TerminalOutput {
     /// No Operation
     NoOperation,
@@ -40,12 +44,46 @@ pub enum TerminalOutput {
     EraseLine,
     /// End of input for half-duplex connections.
     GoAhead,
-    /// Terminal Received Line of Ascii
+    /// End of File.
+    EndOfFile,
+    /// Suspend the current process.
+    Suspend,
+    /// Abort the current process.
+    Abort,
+    /// End of Record, commonly used to mark prompts.
+    EndOfRecord,
+    /// Terminal Received a line, or partial line, of NVT Ascii
     AsciiData(String),
-    /// Terminal Received Binary Data
+    /// Terminal Received Binary Data, while in binary mode
     BinaryData(Vec<u8>),
     /// Current Status of option. May have changed
-    TerminalOptionStatus(TerminalEndpoint, TerminalOption, OptionStatus),
-    /// Received Option Argument
-    TerminalOptionArgument(TerminalEndpoint, TerminalOptionArgument),
+    TerminalOptionStatus(TerminalEndpoint, TelnetOption, OptionStatus),
+    /// Event emitted by a registered `OptionHandler`
+    OptionEvent(TelnetOption, OptionEvent),
+    /// Remote reported a new window size
+    WindowSize {
+        /// Width in characters, zero if unknown
+        width: u16,
+        /// Height in characters, zero if unknown
+        height: u16,
+    },
+    /// Remote finished reporting its terminal types
+    TerminalType(TerminalType),
+    /// Remote answered a request for environment variables
+    Environment(Environment),
+    /// Remote reported environment variables that changed
+    EnvironmentInfo(Environment),
+    /// Text is exchanged in a newly agreed character set
+    Charset(Encoding),
+    /// LINEMODE editing mode changed. Signals trapped by the remote arrive
+    /// as `InterruptProcess`, `Suspend`, `Abort` and `EndOfFile`.
+    Linemode(LinemodeMode),
+    /// Remote changed entries of the LINEMODE special character table
+    SpecialCharacters(Vec<SlcTriplet>),
+    /// Remote asked for input to be forwarded on the characters in the
+    /// mask, or stopped asking when `None`
+    ForwardMask(Option<Vec<u8>>),
+    /// Remote reported the options it considers enabled, from its own
+    /// point of view
+    Status(TelnetOptionStatus),
 }
@@ -14,116 +14,566 @@
 // limitations under the License.
 //
 
-use crate::consts::option::SUPPORT;
-use crate::terminal::options::TerminalOption;
-
-pub struct ConfigurationError;
-
-pub struct NegotiationError;
+use crate::codec::{CodecConfiguration, TelnetFrame, TelnetOption};
+use crate::terminal::option::linemode::LinemodeMode;
+use crate::terminal::option::new_environ::{Environment, EnvironmentVariable};
+use crate::terminal::options::{
+    Compatibility, TerminalEndpoint, TerminalOptionState,
+};
+use crate::terminal::Encoding;
+use std::time::Duration;
+use tracing::{event, Level};
 
 ///
-/// Network Virtual Terminal Configuration
+/// Network Virtual Terminal Option Negotiation State.
+///
+/// Tracks both sides of every option using the Q Method described in
+/// [RFC1143](https://tools.ietf.org/html/rfc1143), which guarantees that
+/// negotiation never loops regardless of what the remote sends.
 ///
-pub struct OptionState {
-    options: [((ConfiguredState, NegotiationState), (ConfiguredState, NegotiationState)); 255],
+/// Each session may accept a different set of options, use
+/// [`TerminalConfiguration::builder`] to describe them.
+///
+#[derive(Clone)]
+pub struct TerminalConfiguration {
+    /// (Local Policy, Remote Policy)
+    policy: [(TerminalOptionState, TerminalOptionState); 256],
+    /// (Local State, Remote State)
+    options: [(NegotiationState, NegotiationState); 256],
+    /// How long to wait for the remote to answer a request
+    timeout: Duration,
+    /// Limits enforced when decoding data from the remote
+    codec: CodecConfiguration,
+    /// Handling of 8-bit data outside of binary mode
+    compatibility: Compatibility,
+    /// How long partial input waits for the rest of its line
+    line_timeout: Duration,
+    /// Terminal types reported when the remote asks
+    terminal_types: Vec<String>,
+    /// Environment variables the remote is allowed to read
+    environment: Environment,
+    /// Character sets offered and accepted through CHARSET
+    charsets: Vec<Encoding>,
+    /// Character set the remote is known to use without negotiation
+    encoding: Option<Encoding>,
+    /// Editing mode requested when the remote enables LINEMODE
+    linemode: LinemodeMode,
 }
 
 impl TerminalConfiguration {
-    pub fn option_local_supported(&self, option: TerminalOption) -> bool {
-        crate::consts::option::SUPPORT[option].0
+    ///
+    /// Create a new `TerminalConfigurationBuilder`
+    ///
+    pub fn builder() -> TerminalConfigurationBuilder {
+        TerminalConfigurationBuilder::default()
+    }
+    ///
+    /// Policy configured for the local side of `option`.
+    ///
+    pub fn local_policy(&self, option: TelnetOption) -> TerminalOptionState {
+        self.policy[usize::from(u8::from(option))].0
+    }
+    ///
+    /// Policy configured for the remote side of `option`.
+    ///
+    pub fn remote_policy(&self, option: TelnetOption) -> TerminalOptionState {
+        self.policy[usize::from(u8::from(option))].1
     }
-    pub fn option_remote_supported(&self, option: TerminalOption) -> bool {
-        crate::consts::option::SUPPORT[option].1
+    ///
+    /// How long to wait for the remote to answer a request.
+    ///
+    pub fn timeout(&self) -> Duration {
+        self.timeout
     }
-    pub fn option_allowed_local(&self, option: TerminalOption) -> bool {
-        self.option_local_supported(option) && (self.options[option].0).0
+    ///
+    /// Limits enforced when decoding data from the remote.
+    ///
+    pub fn codec(&self) -> CodecConfiguration {
+        self.codec
     }
-    pub fn option_allowed_remote(&self, option: TerminalOption) -> bool {
-        self.option_remote_supported(option) && (self.options[option].1).0
+    ///
+    /// Handling of 8-bit data while TRANSMIT-BINARY is off.
+    ///
+    pub fn compatibility(&self) -> Compatibility {
+        self.compatibility
     }
-    pub fn option_enabled_local(&mut self, option: TerminalOption) -> bool {
-        self.options.
+    ///
+    /// How long partial input waits for the rest of its line before it is
+    /// delivered anyway.
+    ///
+    pub fn line_timeout(&self) -> Duration {
+        self.line_timeout
     }
-    pub fn option_enabled_remote(&mut self, option: TerminalOption) -> bool {
-        self.option_remote_supported(option) && (self.options[option].1).0
+    ///
+    /// Terminal types reported, in order, when the remote asks.
+    ///
+    pub fn terminal_types(&self) -> &[String] {
+        &self.terminal_types
+    }
+    ///
+    /// Environment variables the remote is allowed to read. Nothing from
+    /// the process environment is ever sent unless it is listed here.
+    ///
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+    ///
+    /// Character sets offered and accepted through CHARSET, in order of
+    /// preference.
+    ///
+    pub fn charsets(&self) -> &[Encoding] {
+        &self.charsets
+    }
+    ///
+    /// Character set the remote is known to use without negotiation.
+    ///
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+    ///
+    /// Editing mode requested when the remote enables LINEMODE.
+    ///
+    pub fn linemode(&self) -> LinemodeMode {
+        self.linemode
     }
 }
 
 impl TerminalConfiguration {
-    pub fn enable_local(
+    pub(crate) fn set_environment(
         &mut self,
+        variable: EnvironmentVariable,
+        value: Option<String>,
+    ) -> bool {
+        self.environment.insert(variable, value.clone()) != Some(value)
+    }
+    pub(crate) fn option_enabled_local(&self, option: TelnetOption) -> bool {
+        self.local_state(option) == NegotiationState::Yes
+    }
+    pub(crate) fn option_enabled_remote(&self, option: TelnetOption) -> bool {
+        self.remote_state(option) == NegotiationState::Yes
+    }
+    pub(crate) fn option_enabled(
+        &self,
+        endpoint: TerminalEndpoint,
+        option: TelnetOption,
+    ) -> bool {
+        match endpoint {
+            TerminalEndpoint::Local => self.option_enabled_local(option),
+            TerminalEndpoint::Remote => self.option_enabled_remote(option),
+        }
+    }
+    pub(crate) fn local_state(&self, option: TelnetOption) -> NegotiationState {
+        self.options[usize::from(u8::from(option))].0
+    }
+    pub(crate) fn remote_state(
+        &self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> NegotiationState {
+        self.options[usize::from(u8::from(option))].1
     }
-    pub fn disable_local(
+}
+
+impl TerminalConfiguration {
+    /// Ask to start performing `option` locally.
+    pub(crate) fn enable_local(
         &mut self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Send, Action::Will, option)
     }
-    pub fn enable_remote(
+    /// Ask to stop performing `option` locally.
+    pub(crate) fn disable_local(
         &mut self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Send, Action::Wont, option)
     }
-    pub fn disable_remote(
+    /// Ask the remote to start performing `option`.
+    pub(crate) fn enable_remote(
         &mut self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Send, Action::Do, option)
     }
-    pub fn receive_do(
+    /// Ask the remote to stop performing `option`.
+    pub(crate) fn disable_remote(
         &mut self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Send, Action::Dont, option)
     }
-    pub fn receive_dont(
+    /// Handle `IAC DO option` from the remote.
+    pub(crate) fn receive_do(
         &mut self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Recv, Action::Do, option)
     }
-    pub fn receive_dowill(
+    /// Handle `IAC DONT option` from the remote.
+    pub(crate) fn receive_dont(
         &mut self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Recv, Action::Dont, option)
     }
-    pub fn receive_wont(
+    /// Handle `IAC WILL option` from the remote.
+    pub(crate) fn receive_will(
         &mut self,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Recv, Action::Will, option)
     }
-    /// Handle Incoming
+    /// Handle `IAC WONT option` from the remote.
+    pub(crate) fn receive_wont(
+        &mut self,
+        option: TelnetOption,
+    ) -> Option<TelnetFrame> {
+        self.handle(Source::Recv, Action::Wont, option)
+    }
+    ///
+    /// Apply a single negotiation event to the option table and return the
+    /// frame, if any, that must be sent to the remote in reply.
+    ///
     fn handle(
         &mut self,
+        source: Source,
         action: Action,
         option: TelnetOption,
-    ) -> Result<Option<TelnetFrame>, NegotiationError> {
-        use self::{Action::*, Source::*, State::*};
-        // @formatter:off
-        #[rustfmt::skip]
-        match (self.options[option].0, self.options[option].1, source, action) {
-        //  (Local State, Remote State, Source, Action) => {  }
-            (         No,            _,   Recv,       Do) => { self.options[option].0 = WantYes;
-                                                               Ok(Some(TelnetFrame::Will(option)))}
-            (         No,            _,   Recv,     Dont) => { Ok(None) /* Ignore */ }
-            (_,_,_) => {panic!("Incomplete")}
+    ) -> Option<TelnetFrame> {
+        let index = usize::from(u8::from(option));
+        // Local options are requested with WILL/WONT and answered with
+        // DO/DONT, Remote options are the reverse.
+        let (endpoint, enable) = match (source, action) {
+            (Source::Send, Action::Will) => (TerminalEndpoint::Local, true),
+            (Source::Send, Action::Wont) => (TerminalEndpoint::Local, false),
+            (Source::Send, Action::Do) => (TerminalEndpoint::Remote, true),
+            (Source::Send, Action::Dont) => (TerminalEndpoint::Remote, false),
+            (Source::Recv, Action::Do) => (TerminalEndpoint::Local, true),
+            (Source::Recv, Action::Dont) => (TerminalEndpoint::Local, false),
+            (Source::Recv, Action::Will) => (TerminalEndpoint::Remote, true),
+            (Source::Recv, Action::Wont) => (TerminalEndpoint::Remote, false),
+        };
+        let (state, policy) = match endpoint {
+            TerminalEndpoint::Local => {
+                (&mut self.options[index].0, self.policy[index].0)
+            }
+            TerminalEndpoint::Remote => {
+                (&mut self.options[index].1, self.policy[index].1)
+            }
+        };
+        if source == Source::Send
+            && enable
+            && policy == TerminalOptionState::Unsupported
+        {
+            event!(
+                Level::WARN,
+                "Refusing to request unsupported {:?} {:?}",
+                endpoint,
+                option,
+            );
+            return None;
+        }
+        let allowed = policy == TerminalOptionState::Allowed
+            || policy == TerminalOptionState::RequestOnConnect;
+        if source == Source::Recv
+            && enable
+            && (*state == NegotiationState::WantNo
+                || *state == NegotiationState::WantNoOpposite)
+        {
+            event!(
+                Level::WARN,
+                "Remote answered our refusal of {:?} with {:?}",
+                option,
+                action,
+            );
+        }
+        let (next, reply) = negotiate(*state, source, enable, allowed);
+        *state = next;
+        reply.map(|positive| match (endpoint, positive) {
+            (TerminalEndpoint::Local, true) => TelnetFrame::Will(option),
+            (TerminalEndpoint::Local, false) => TelnetFrame::Wont(option),
+            (TerminalEndpoint::Remote, true) => TelnetFrame::Do(option),
+            (TerminalEndpoint::Remote, false) => TelnetFrame::Dont(option),
+        })
+    }
+}
+
+impl TerminalConfiguration {
+    ///
+    /// Request every option configured with
+    /// [`TerminalOptionState::RequestOnConnect`], returning the frames to
+    /// send to the remote.
+    ///
+    pub(crate) fn connect(&mut self) -> Vec<TelnetFrame> {
+        let mut frames = Vec::new();
+        for code in 0..=255u8 {
+            let option = TelnetOption::from(code);
+            let (local, remote) = self.policy[usize::from(code)];
+            if local == TerminalOptionState::RequestOnConnect {
+                frames.extend(self.enable_local(option));
+            }
+            if remote == TerminalOptionState::RequestOnConnect {
+                frames.extend(self.enable_remote(option));
+            }
+        }
+        frames
+    }
+}
+
+impl Default for TerminalConfiguration {
+    fn default() -> TerminalConfiguration {
+        TerminalConfiguration::builder().build()
+    }
+}
+
+///
+/// Builder for a per session [`TerminalConfiguration`].
+///
+/// Every option starts as [`TerminalOptionState::Supported`] on both sides.
+///
+#[derive(Clone)]
+pub struct TerminalConfigurationBuilder {
+    policy: [(TerminalOptionState, TerminalOptionState); 256],
+    timeout: Duration,
+    codec: CodecConfiguration,
+    compatibility: Compatibility,
+    line_timeout: Duration,
+    terminal_types: Vec<String>,
+    environment: Environment,
+    charsets: Vec<Encoding>,
+    encoding: Option<Encoding>,
+    linemode: LinemodeMode,
+}
+
+impl TerminalConfigurationBuilder {
+    ///
+    /// Set the policy for the local side of `option`.
+    ///
+    pub fn local(
+        mut self,
+        option: TelnetOption,
+        state: TerminalOptionState,
+    ) -> TerminalConfigurationBuilder {
+        self.policy[usize::from(u8::from(option))].0 = state;
+        self
+    }
+    ///
+    /// Set the policy for the remote side of `option`.
+    ///
+    pub fn remote(
+        mut self,
+        option: TelnetOption,
+        state: TerminalOptionState,
+    ) -> TerminalConfigurationBuilder {
+        self.policy[usize::from(u8::from(option))].1 = state;
+        self
+    }
+    ///
+    /// Set the policy for both sides of `option`.
+    ///
+    pub fn option(
+        self,
+        option: TelnetOption,
+        state: TerminalOptionState,
+    ) -> TerminalConfigurationBuilder {
+        self.local(option, state).remote(option, state)
+    }
+    ///
+    /// Set how long to wait for the remote to answer a request.
+    ///
+    pub fn timeout(
+        mut self,
+        timeout: Duration,
+    ) -> TerminalConfigurationBuilder {
+        self.timeout = timeout;
+        self
+    }
+    ///
+    /// Set the limits enforced when decoding data from the remote.
+    ///
+    pub fn codec(
+        mut self,
+        codec: CodecConfiguration,
+    ) -> TerminalConfigurationBuilder {
+        self.codec = codec;
+        self
+    }
+    ///
+    /// Set the handling of 8-bit data while TRANSMIT-BINARY is off.
+    ///
+    pub fn compatibility(
+        mut self,
+        compatibility: Compatibility,
+    ) -> TerminalConfigurationBuilder {
+        self.compatibility = compatibility;
+        self
+    }
+    ///
+    /// Set how long partial input waits for the rest of its line.
+    ///
+    pub fn line_timeout(
+        mut self,
+        line_timeout: Duration,
+    ) -> TerminalConfigurationBuilder {
+        self.line_timeout = line_timeout;
+        self
+    }
+    ///
+    /// Set the terminal types reported, in order, when the remote asks. The
+    /// local side of TTYPE must still be allowed for the remote to ask.
+    ///
+    pub fn terminal_types<I, T>(
+        mut self,
+        terminal_types: I,
+    ) -> TerminalConfigurationBuilder
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.terminal_types =
+            terminal_types.into_iter().map(Into::into).collect();
+        self
+    }
+    ///
+    /// Allow the remote to read `variable`, reporting it as `value`.
+    ///
+    pub fn environment(
+        mut self,
+        variable: EnvironmentVariable,
+        value: &str,
+    ) -> TerminalConfigurationBuilder {
+        self.environment.insert(variable, Some(value.to_string()));
+        self
+    }
+    ///
+    /// Set the character sets offered and accepted through CHARSET, in
+    /// order of preference.
+    ///
+    pub fn charsets(
+        mut self,
+        charsets: Vec<Encoding>,
+    ) -> TerminalConfigurationBuilder {
+        self.charsets = charsets;
+        self
+    }
+    ///
+    /// Exchange text with the remote in `encoding` from the start, without
+    /// negotiating it. A character set agreed through CHARSET later still
+    /// takes over.
+    ///
+    pub fn encoding(
+        mut self,
+        encoding: Encoding,
+    ) -> TerminalConfigurationBuilder {
+        self.encoding = Some(encoding);
+        self
+    }
+    ///
+    /// Set the editing mode requested when the remote enables LINEMODE.
+    ///
+    pub fn linemode(
+        mut self,
+        linemode: LinemodeMode,
+    ) -> TerminalConfigurationBuilder {
+        self.linemode = linemode;
+        self
+    }
+    ///
+    /// Build the `TerminalConfiguration`
+    ///
+    pub fn build(self) -> TerminalConfiguration {
+        TerminalConfiguration {
+            policy: self.policy,
+            options: [(NegotiationState::No, NegotiationState::No); 256],
+            timeout: self.timeout,
+            codec: self.codec,
+            compatibility: self.compatibility,
+            line_timeout: self.line_timeout,
+            terminal_types: self.terminal_types,
+            environment: self.environment,
+            charsets: self.charsets,
+            encoding: self.encoding,
+            linemode: self.linemode,
         }
-        // @formatter:on
     }
 }
 
-impl Default for OptionManager {
-    fn default() -> OptionManager {
-        OptionManager {
-            options: [(State::No, State::No); 255],
-            enabled: [(false, false); 255],
+impl Default for TerminalConfigurationBuilder {
+    fn default() -> TerminalConfigurationBuilder {
+        TerminalConfigurationBuilder {
+            policy: [(
+                TerminalOptionState::default(),
+                TerminalOptionState::default(),
+            ); 256],
+            timeout: Duration::from_secs(30),
+            codec: CodecConfiguration::default(),
+            compatibility: Compatibility::default(),
+            line_timeout: Duration::from_millis(100),
+            terminal_types: Vec::new(),
+            environment: Environment::new(),
+            charsets: Vec::new(),
+            encoding: None,
+            linemode: LinemodeMode {
+                edit: true,
+                trapsig: true,
+                ..LinemodeMode::default()
+            },
         }
     }
 }
 
+///
+/// Q Method transition table for a single side of an option.
+///
+/// Returns the next state and whether a positive (`WILL`/`DO`) or negative
+/// (`WONT`/`DONT`) reply must be sent. The table is identical for both sides,
+/// only the reply verbs differ.
+///
+#[rustfmt::skip]
+fn negotiate(
+    state: NegotiationState,
+    source: Source,
+    enable: bool,
+    allowed: bool,
+) -> (NegotiationState, Option<bool>) {
+    use self::{NegotiationState::*, Source::*};
+    match (state, source, enable) {
+    //  (           State, Source, Enable) => (     Next State,     Reply)
+        (              No,   Recv,   true) if allowed => (  Yes, Some(true)),
+        (              No,   Recv,   true) => (             No, Some(false)),
+        (              No,   Recv,  false) => (             No,        None),
+        (             Yes,   Recv,   true) => (            Yes,        None),
+        (             Yes,   Recv,  false) => (             No, Some(false)),
+        (          WantNo,   Recv,   true) => (             No,        None),
+        (          WantNo,   Recv,  false) => (             No,        None),
+        (  WantNoOpposite,   Recv,   true) => (            Yes,        None),
+        (  WantNoOpposite,   Recv,  false) => (        WantYes,  Some(true)),
+        (         WantYes,   Recv,   true) => (            Yes,        None),
+        (         WantYes,   Recv,  false) => (             No,        None),
+        ( WantYesOpposite,   Recv,   true) => (         WantNo, Some(false)),
+        ( WantYesOpposite,   Recv,  false) => (             No,        None),
+        (              No,   Send,   true) => (        WantYes,  Some(true)),
+        (              No,   Send,  false) => (             No,        None),
+        (             Yes,   Send,   true) => (            Yes,        None),
+        (             Yes,   Send,  false) => (         WantNo, Some(false)),
+        (          WantNo,   Send,   true) => ( WantNoOpposite,        None),
+        (          WantNo,   Send,  false) => (         WantNo,        None),
+        (  WantNoOpposite,   Send,   true) => ( WantNoOpposite,        None),
+        (  WantNoOpposite,   Send,  false) => (         WantNo,        None),
+        (         WantYes,   Send,   true) => (        WantYes,        None),
+        (         WantYes,   Send,  false) => (WantYesOpposite,        None),
+        ( WantYesOpposite,   Send,   true) => (        WantYes,        None),
+        ( WantYesOpposite,   Send,  false) => (WantYesOpposite,        None),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Source {
     Send,
     Recv,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Action {
     Do,
     Dont,
@@ -131,17 +581,151 @@ enum Action {
     Wont,
 }
 
-enum NegotiationState {
+///
+/// RFC1143 Option State
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NegotiationState {
+    /// Option is disabled
     No,
+    /// Sent a negative request, awaiting the answer
     WantNo,
+    /// Sent a negative request, will ask to enable once answered
     WantNoOpposite,
+    /// Option is enabled
     Yes,
+    /// Sent a positive request, awaiting the answer
     WantYes,
+    /// Sent a positive request, will ask to disable once answered
     WantYesOpposite,
 }
 
-enum ConfiguredState {
-    Unsupported,
-    Supported,
-    Allowed,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::{NegotiationState, TerminalConfiguration};
+    use crate::codec::{TelnetFrame, TelnetOption};
+    use crate::terminal::options::TerminalOptionState;
+
+    const OPTION: TelnetOption = TelnetOption::SuppressGoAhead;
+
+    fn supported() -> TerminalConfiguration {
+        TerminalConfiguration::builder()
+            .option(OPTION, TerminalOptionState::Allowed)
+            .build()
+    }
+
+    #[test]
+    fn refuse_unsupported() {
+        let mut config = TerminalConfiguration::default();
+        assert_eq!(config.receive_do(OPTION), Some(TelnetFrame::Wont(OPTION)));
+        assert_eq!(
+            config.receive_will(OPTION),
+            Some(TelnetFrame::Dont(OPTION))
+        );
+        assert_eq!(config.receive_dont(OPTION), None);
+        assert_eq!(config.receive_wont(OPTION), None);
+        assert!(!config.option_enabled_local(OPTION));
+        assert!(!config.option_enabled_remote(OPTION));
+    }
+
+    #[test]
+    fn accept_supported_without_looping() {
+        let mut config = supported();
+        assert_eq!(config.receive_do(OPTION), Some(TelnetFrame::Will(OPTION)));
+        assert_eq!(config.receive_do(OPTION), None);
+        assert_eq!(config.receive_will(OPTION), Some(TelnetFrame::Do(OPTION)));
+        assert_eq!(config.receive_will(OPTION), None);
+        assert!(config.option_enabled_local(OPTION));
+        assert!(config.option_enabled_remote(OPTION));
+        assert_eq!(
+            config.receive_dont(OPTION),
+            Some(TelnetFrame::Wont(OPTION))
+        );
+        assert_eq!(config.receive_dont(OPTION), None);
+        assert!(!config.option_enabled_local(OPTION));
+    }
+
+    #[test]
+    fn local_request_answered() {
+        let mut config = TerminalConfiguration::default();
+        assert_eq!(config.enable_remote(OPTION), Some(TelnetFrame::Do(OPTION)));
+        assert_eq!(config.enable_remote(OPTION), None);
+        assert_eq!(config.remote_state(OPTION), NegotiationState::WantYes);
+        assert_eq!(config.receive_will(OPTION), None);
+        assert!(config.option_enabled_remote(OPTION));
+        assert_eq!(
+            config.disable_remote(OPTION),
+            Some(TelnetFrame::Dont(OPTION))
+        );
+        assert_eq!(config.receive_wont(OPTION), None);
+        assert_eq!(config.remote_state(OPTION), NegotiationState::No);
+    }
+
+    #[test]
+    fn local_request_refused() {
+        let mut config = TerminalConfiguration::default();
+        assert_eq!(
+            config.enable_local(OPTION),
+            Some(TelnetFrame::Will(OPTION))
+        );
+        assert_eq!(config.receive_dont(OPTION), None);
+        assert_eq!(config.local_state(OPTION), NegotiationState::No);
+    }
+
+    #[test]
+    fn opposite_request_queued() {
+        let mut config = TerminalConfiguration::default();
+        assert_eq!(
+            config.enable_local(OPTION),
+            Some(TelnetFrame::Will(OPTION))
+        );
+        assert_eq!(config.disable_local(OPTION), None);
+        assert_eq!(
+            config.local_state(OPTION),
+            NegotiationState::WantYesOpposite
+        );
+        assert_eq!(config.receive_do(OPTION), Some(TelnetFrame::Wont(OPTION)));
+        assert_eq!(config.local_state(OPTION), NegotiationState::WantNo);
+        assert_eq!(config.enable_local(OPTION), None);
+        assert_eq!(
+            config.receive_dont(OPTION),
+            Some(TelnetFrame::Will(OPTION))
+        );
+        assert_eq!(config.local_state(OPTION), NegotiationState::WantYes);
+        assert_eq!(config.receive_do(OPTION), None);
+        assert!(config.option_enabled_local(OPTION));
+    }
+
+    #[test]
+    fn refuse_unsupported_request() {
+        let mut config = TerminalConfiguration::builder()
+            .local(OPTION, TerminalOptionState::Unsupported)
+            .build();
+        assert_eq!(config.enable_local(OPTION), None);
+        assert_eq!(config.local_state(OPTION), NegotiationState::No);
+        assert_eq!(config.enable_remote(OPTION), Some(TelnetFrame::Do(OPTION)));
+    }
+
+    #[test]
+    fn request_on_connect() {
+        let mut config = TerminalConfiguration::builder()
+            .local(TelnetOption::Echo, TerminalOptionState::RequestOnConnect)
+            .remote(TelnetOption::NAWS, TerminalOptionState::RequestOnConnect)
+            .remote(TelnetOption::TTYPE, TerminalOptionState::Allowed)
+            .build();
+        assert_eq!(
+            config.connect(),
+            vec![
+                TelnetFrame::Will(TelnetOption::Echo),
+                TelnetFrame::Do(TelnetOption::NAWS),
+            ]
+        );
+        assert_eq!(config.receive_will(TelnetOption::NAWS), None);
+        assert_eq!(
+            config.receive_will(TelnetOption::TTYPE),
+            Some(TelnetFrame::Do(TelnetOption::TTYPE))
+        );
+        assert!(config.option_enabled_remote(TelnetOption::NAWS));
+        assert!(config.option_enabled_remote(TelnetOption::TTYPE));
+    }
+}
@@ -14,146 +14,1772 @@
 // limitations under the License.
 //
 
-use crate::consts;
-use crate::codec::{DecodeError, EncodeError, TelnetCodec, TelnetFrame};
-use crate::terminal::{TerminalError, TerminalInput, TerminalOutput};
-use crate::terminal::option::TelnetOption;
+use crate::codec::{
+    Argument, EncodeError, TelnetCodec, TelnetFrame, TelnetOption,
+};
+use crate::terminal::line;
+use crate::terminal::nvt::{self, NewlineDecoder};
+use crate::terminal::option::charset::Charset;
+use crate::terminal::option::linemode::{
+    default_slc_table, ForwardMask, Linemode, LinemodeMode, SlcFunction,
+    SlcLevel, SlcTable, SlcTriplet,
+};
+use crate::terminal::option::naws::NAWS;
+use crate::terminal::option::new_environ::{
+    Environment, EnvironmentVariable, NewEnviron,
+};
+use crate::terminal::option::old_environ::OldEnviron;
+use crate::terminal::option::status::{Status, TelnetOptionStatus};
+use crate::terminal::option::ttype::{TerminalType, TTYPE};
+use crate::terminal::option::TerminalOptionState;
+use crate::terminal::state::{NegotiationState, TerminalConfiguration};
+use crate::terminal::{
+    EightBitPolicy, Encoding, HandlerContext, InputMode, OptionHandler,
+    OptionStatus, TerminalEndpoint, TerminalError, TerminalInput,
+    TerminalOutput,
+};
 
-use bytes::{BytesMut, BufMut};
+use bytes::{Bytes, BytesMut};
+use futures::future;
+use futures::ready;
 use futures::task::{Context, Poll};
-use futures::{Sink, Stream, Future};
+use futures::{Sink, Stream};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
 use std::pin::Pin;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time;
 use tokio_util::codec::Framed;
-use crate::terminal::arguments::status::TelnetOptionStatus;
-use std::ops::Neg;
-use crate::terminal::options::TerminalOption;
+use tracing::{event, Level};
 
+///
+/// Amount of received data buffered before it is emitted as `BinaryData`.
+///
+const BUFFER_SIZE: usize = 8 * 4096;
+
+///
+/// Most terminal types requested before a remote that never repeats itself
+/// is considered done.
+///
+const MAX_TERMINAL_TYPES: usize = 16;
+
+///
+/// Options carrying environment variables, in order of preference.
+///
+const ENVIRONMENT_OPTIONS: [TelnetOption; 2] =
+    [TelnetOption::NewEnvironment, TelnetOption::Environment];
 
 ///
 /// Telnet Network Virtual Terminal Endpoint
 ///
 pub struct NetworkVirtualTerminal<S>
-    where
-        S: AsyncWrite + AsyncRead,
+where
+    S: AsyncWrite + AsyncRead,
 {
     /// Codec Wrapped Stream
     framed: Framed<S, TelnetCodec>,
     /// Terminal Input Buffer
     buffer: BytesMut,
+    /// NVT newline translation for data from the remote
+    newlines: NewlineDecoder,
+    /// Remote has sent 8-bit data since binary mode was last left
+    eight_bit: bool,
+    /// Fires when partial input has waited long enough to be delivered
+    idle: Option<time::Delay>,
+    /// Whether input is delivered by line or by character
+    input_mode: InputMode,
+    /// Text received while reading a secret
+    secret: Option<String>,
+    /// Window size last reported by the remote
+    window_size: Option<NAWS>,
+    /// Window size reported to the remote
+    local_window_size: Option<NAWS>,
+    /// Terminal types reported by the remote once it started repeating
+    terminal_type: Option<TerminalType>,
+    /// Terminal types received while still requesting more
+    terminal_types: Option<TerminalType>,
+    /// Position of the next terminal type reported to the remote
+    terminal_type_index: usize,
+    /// Environment variables reported by the remote
+    environment: Environment,
+    /// Remote exchanges the VAR and VALUE codes of ENVIRON
+    environ_swapped: bool,
+    /// Character set agreed for text
+    charset: Option<Encoding>,
+    /// A CHARSET request is waiting for an answer
+    charset_requested: bool,
+    /// LINEMODE editing mode both sides agreed on
+    linemode: LinemodeMode,
+    /// LINEMODE editing mode sent to the remote and not yet acknowledged
+    linemode_proposed: Option<LinemodeMode>,
+    /// LINEMODE special character table
+    special_characters: SlcTable,
+    /// Characters the server asked to have input forwarded on
+    forward_mask: Option<Vec<u8>>,
     /// Current Option Negotiation State
-    options: [((bool, NegotiationState), (bool, NegotiationState)); 255],
+    options: TerminalConfiguration,
+    /// Negotiation Replies waiting to be sent to the remote
+    replies: VecDeque<TelnetFrame>,
+    /// TIMING-MARK answers waiting for earlier output to be flushed
+    timing_mark_replies: VecDeque<TelnetFrame>,
+    /// TIMING-MARK requests sent to the remote
+    timing_marks_sent: u64,
+    /// TIMING-MARK requests still waited on, and whether each was answered
+    timing_marks: VecDeque<(u64, bool)>,
+    /// Terminal Output waiting to be returned from the Stream
+    pending: VecDeque<TerminalOutput>,
+    /// Registered Option Handlers
+    handlers: HashMap<TelnetOption, Box<dyn OptionHandler>>,
 }
 
 impl<S> NetworkVirtualTerminal<S>
-    where
-        S: AsyncWrite + AsyncRead,
+where
+    S: AsyncWrite + AsyncRead + Unpin,
 {
     pub fn new(stream: S) -> NetworkVirtualTerminal<S> {
+        NetworkVirtualTerminal::with_configuration(
+            stream,
+            TerminalConfiguration::default(),
+        )
+    }
+    ///
+    /// Create a new `NetworkVirtualTerminal` negotiating options according to
+    /// `configuration`. Options configured to be requested on connect are
+    /// sent the first time the terminal is polled.
+    ///
+    pub fn with_configuration(
+        stream: S,
+        mut configuration: TerminalConfiguration,
+    ) -> NetworkVirtualTerminal<S> {
+        let replies = configuration.connect().into_iter().collect();
+        let codec = TelnetCodec::with_configuration(configuration.codec());
         NetworkVirtualTerminal {
-            framed: Framed::new(stream, TelnetCodec::default()),
-            buffer: BytesMut::with_capacity(8 * 4096),
-            options: [(NegotationState::No, NegotationState::No); 255],
+            framed: Framed::new(stream, codec),
+            buffer: BytesMut::with_capacity(BUFFER_SIZE),
+            newlines: NewlineDecoder::default(),
+            eight_bit: false,
+            idle: None,
+            input_mode: InputMode::Line,
+            secret: None,
+            window_size: None,
+            local_window_size: None,
+            terminal_type: None,
+            terminal_types: None,
+            terminal_type_index: 0,
+            environment: Environment::new(),
+            environ_swapped: false,
+            charset: configuration.encoding(),
+            charset_requested: false,
+            linemode: LinemodeMode::default(),
+            linemode_proposed: None,
+            special_characters: default_slc_table(),
+            forward_mask: None,
+            options: configuration,
+            replies,
+            timing_mark_replies: VecDeque::new(),
+            timing_marks_sent: 0,
+            timing_marks: VecDeque::new(),
+            pending: VecDeque::new(),
+            handlers: HashMap::new(),
         }
     }
-
-
-    /// TODO: Figure out State engine for Negotiation
-    fn receive_negotiation(&mut self, action: Action, option: TelnetOption) {
-        use self::{State::*, Action::*};
-        // https://mudcoders.fandom.com/wiki/Telnet_Option_Negotiation_-_Q_Method
-        // @formatter:off
-        #[rustfmt::skip]
-        match (self.options[option].0, self.options[option].1, action) {
-        //  (     Local State,    Remote State, Action) => { Action }
-            (              No,               _,     Do) => { self.options[option].0 = Yes; }
-            (              No,               _,   Dont) => { /* Ignored */ }
-            (               _,              No,   Will) => { self.options[option].1 = Yes; }
-            (               _,              No,   Wont) => { }
-        //    (Recv,   Will,               _,              No) => { self.options[option].1 = NegotiationState::WantYes }
-        //    (Recv,   Wont,               _,              No) => { /* Do Nothing */ }
-        //
+    ///
+    /// Register `handler` to be notified about `option`, replacing any
+    /// handler previously registered for it.
+    ///
+    /// The option must still be permitted by the `TerminalConfiguration`
+    /// before the remote is allowed to enable it.
+    ///
+    /// Subnegotiations of the options the terminal implements itself, NAWS,
+    /// TTYPE, NEW-ENVIRON, ENVIRON, CHARSET, LINEMODE and STATUS, are
+    /// always handled by the terminal. A handler registered for one of them
+    /// is only told when it is enabled or disabled.
+    ///
+    pub fn register<H>(&mut self, option: TelnetOption, handler: H)
+    where
+        H: OptionHandler + 'static,
+    {
+        self.handlers.insert(option, Box::new(handler));
+    }
+    ///
+    /// Is `option` currently enabled on the local side.
+    ///
+    pub fn local_enabled(&self, option: TelnetOption) -> bool {
+        self.options.option_enabled_local(option)
+    }
+    ///
+    /// Is `option` currently enabled on the remote side.
+    ///
+    pub fn remote_enabled(&self, option: TelnetOption) -> bool {
+        self.options.option_enabled_remote(option)
+    }
+    ///
+    /// Ask to start performing `option` locally, resolving to `true` once the
+    /// remote agrees or `false` if it refuses.
+    ///
+    pub async fn enable_local(
+        &mut self,
+        option: TelnetOption,
+    ) -> Result<bool, TerminalError> {
+        self.request(TerminalEndpoint::Local, option, true).await
+    }
+    ///
+    /// Ask to stop performing `option` locally, resolving to `true` once the
+    /// remote has acknowledged it.
+    ///
+    pub async fn disable_local(
+        &mut self,
+        option: TelnetOption,
+    ) -> Result<bool, TerminalError> {
+        self.request(TerminalEndpoint::Local, option, false).await
+    }
+    ///
+    /// Ask the remote to start performing `option`, resolving to `true` once
+    /// the remote agrees or `false` if it refuses.
+    ///
+    pub async fn enable_remote(
+        &mut self,
+        option: TelnetOption,
+    ) -> Result<bool, TerminalError> {
+        self.request(TerminalEndpoint::Remote, option, true).await
+    }
+    ///
+    /// Ask the remote to stop performing `option`, resolving to `true` once
+    /// the remote has acknowledged it.
+    ///
+    pub async fn disable_remote(
+        &mut self,
+        option: TelnetOption,
+    ) -> Result<bool, TerminalError> {
+        self.request(TerminalEndpoint::Remote, option, false).await
+    }
+    ///
+    /// Window size last reported by the remote as `(width, height)`.
+    ///
+    pub fn window_size(&self) -> Option<(u16, u16)> {
+        self.window_size.map(|size| (size.width, size.height))
+    }
+    ///
+    /// Report a new local window size to the remote, as soon as NAWS is
+    /// enabled locally.
+    ///
+    pub fn set_window_size(&mut self, width: u16, height: u16) {
+        let size = NAWS::new(width, height);
+        if self.local_window_size == Some(size) {
+            return;
+        }
+        self.local_window_size = Some(size);
+        if self.local_enabled(TelnetOption::NAWS) {
+            self.send_window_size(size);
         }
-        // @formatter:on
     }
-}
-
-impl<S> Stream for NetworkVirtualTerminal<S>
-    where
-        S: AsyncRead,
-{
-    type Item = TerminalOutput;
-
-    fn poll_next(
-        self: Pin<&mut Self>,
+    ///
+    /// Queue a NAWS subnegotiation reporting `size`.
+    ///
+    fn send_window_size(&mut self, size: NAWS) {
+        self.send_argument(TelnetOption::NAWS, &size);
+    }
+    ///
+    /// Terminal types reported by the remote, once it has sent them all.
+    ///
+    pub fn terminal_type(&self) -> Option<&TerminalType> {
+        self.terminal_type.as_ref()
+    }
+    ///
+    /// Queue a subnegotiation of `option` carrying `argument`.
+    ///
+    fn send_argument<A: Argument>(
+        &mut self,
+        option: TelnetOption,
+        argument: &A,
+    ) {
+        let mut arguments = Vec::with_capacity(argument.len());
+        argument.encode(&mut arguments);
+        self.replies.push_back(TelnetFrame::Subnegotiate(
+            option,
+            Bytes::from(arguments),
+        ));
+    }
+    ///
+    /// Handle a TTYPE subnegotiation, requesting further names from the
+    /// remote until it repeats one, and answering its requests from the
+    /// configured terminal types.
+    ///
+    fn receive_terminal_type(
+        &mut self,
+        ttype: TTYPE,
+    ) -> Option<TerminalOutput> {
+        match ttype {
+            TTYPE::Is(name) => {
+                let received = self.terminal_types.as_mut()?;
+                if received.push(name)
+                    && received.names.len() < MAX_TERMINAL_TYPES
+                {
+                    self.send_argument(TelnetOption::TTYPE, &TTYPE::Send);
+                    return None;
+                }
+                let received = self.terminal_types.take()?;
+                self.terminal_type = Some(received.clone());
+                Some(TerminalOutput::TerminalType(received))
+            }
+            TTYPE::Send => {
+                if !self.local_enabled(TelnetOption::TTYPE) {
+                    return None;
+                }
+                // The last name is sent twice to mark the end of the list,
+                // after which the list starts over.
+                let names = self.options.terminal_types();
+                let name = match names.len() {
+                    0 => "UNKNOWN".to_string(),
+                    len => names[self.terminal_type_index.min(len - 1)].clone(),
+                };
+                self.terminal_type_index =
+                    if self.terminal_type_index < names.len() {
+                        self.terminal_type_index + 1
+                    } else {
+                        0
+                    };
+                self.send_argument(TelnetOption::TTYPE, &TTYPE::Is(name));
+                None
+            }
+        }
+    }
+    ///
+    /// Environment variables reported by the remote so far.
+    ///
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+    ///
+    /// Ask the remote for `variables`, where an empty name requests every
+    /// variable of its kind and an empty list requests everything. Uses
+    /// NEW-ENVIRON when the remote supports it and falls back on ENVIRON.
+    /// Returns `false` if the remote has enabled neither.
+    ///
+    pub fn request_environment(
+        &mut self,
+        variables: Vec<EnvironmentVariable>,
+    ) -> bool {
+        let option = ENVIRONMENT_OPTIONS
+            .iter()
+            .copied()
+            .find(|option| self.remote_enabled(*option));
+        match option {
+            Some(option) => {
+                self.send_environment(option, NewEnviron::Send(variables));
+                true
+            }
+            None => false,
+        }
+    }
+    ///
+    /// Allow the remote to read `variable`, or report it as undefined when
+    /// `value` is `None`. The remote is told about changes while either
+    /// environment option is enabled locally.
+    ///
+    pub fn set_environment(
+        &mut self,
+        variable: EnvironmentVariable,
+        value: Option<String>,
+    ) {
+        if !self
+            .options
+            .set_environment(variable.clone(), value.clone())
+        {
+            return;
+        }
+        let mut changed = Environment::new();
+        changed.insert(variable, value);
+        for option in ENVIRONMENT_OPTIONS.iter().copied() {
+            if self.local_enabled(option) {
+                self.send_environment(
+                    option,
+                    NewEnviron::Info(changed.clone()),
+                );
+            }
+        }
+    }
+    ///
+    /// Queue `message` for `option`, in the VAR and VALUE convention the
+    /// remote uses for ENVIRON.
+    ///
+    fn send_environment(&mut self, option: TelnetOption, message: NewEnviron) {
+        if option == TelnetOption::Environment {
+            let swapped = self.environ_swapped;
+            self.send_argument(option, &OldEnviron::new(message, swapped));
+        } else {
+            self.send_argument(option, &message);
+        }
+    }
+    ///
+    /// Handle a NEW-ENVIRON or ENVIRON subnegotiation, recording the
+    /// variables the remote reports and answering its requests from the
+    /// configured environment.
+    ///
+    fn receive_environment(
+        &mut self,
+        option: TelnetOption,
+        message: NewEnviron,
+    ) -> Option<TerminalOutput> {
+        match message {
+            NewEnviron::Is(variables) | NewEnviron::Info(variables)
+                if !self.remote_enabled(option) =>
+            {
+                event!(
+                    Level::DEBUG,
+                    "Ignoring {} unrequested environment variables",
+                    variables.len()
+                );
+                None
+            }
+            NewEnviron::Is(variables) => {
+                self.environment.extend(variables.clone());
+                Some(TerminalOutput::Environment(variables))
+            }
+            NewEnviron::Info(variables) => {
+                self.environment.extend(variables.clone());
+                Some(TerminalOutput::EnvironmentInfo(variables))
+            }
+            NewEnviron::Send(requested) => {
+                if self.local_enabled(option) {
+                    let reply = self.select_environment(&requested);
+                    self.send_environment(option, NewEnviron::Is(reply));
+                }
+                None
+            }
+        }
+    }
+    ///
+    /// Variables from the configured environment matching `requested`,
+    /// requested variables that are not configured are reported as
+    /// undefined.
+    ///
+    fn select_environment(
+        &self,
+        requested: &[EnvironmentVariable],
+    ) -> Environment {
+        let allowed = self.options.environment();
+        if requested.is_empty() {
+            return allowed.clone();
+        }
+        let mut selected = Environment::new();
+        for request in requested {
+            let matching = allowed
+                .iter()
+                .filter(|(variable, _)| variable.matches(request))
+                .map(|(variable, value)| (variable.clone(), value.clone()));
+            selected.extend(matching);
+            if !request.name().is_empty() {
+                selected.entry(request.clone()).or_insert(None);
+            }
+        }
+        selected
+    }
+    ///
+    /// Character set text is exchanged in, if one was agreed.
+    ///
+    pub fn charset(&self) -> Option<Encoding> {
+        self.charset
+    }
+    ///
+    /// Offer the configured character sets to the remote. Returns `false`
+    /// if CHARSET is not enabled or no character sets are configured.
+    ///
+    pub fn request_charset(&mut self) -> bool {
+        let enabled = self.local_enabled(TelnetOption::Charset)
+            || self.remote_enabled(TelnetOption::Charset);
+        if !enabled || self.options.charsets().is_empty() {
+            return false;
+        }
+        let names = self
+            .options
+            .charsets()
+            .iter()
+            .map(|charset| charset.name().to_string())
+            .collect();
+        self.send_argument(TelnetOption::Charset, &Charset::Request(names));
+        self.charset_requested = true;
+        true
+    }
+    ///
+    /// Handle a CHARSET subnegotiation, answering requests with the first
+    /// offered character set that is also configured locally.
+    ///
+    fn receive_charset(&mut self, message: Charset) -> Option<TerminalOutput> {
+        let enabled = self.local_enabled(TelnetOption::Charset)
+            || self.remote_enabled(TelnetOption::Charset);
+        if !enabled {
+            event!(
+                Level::DEBUG,
+                "Ignoring {:?}, CHARSET is not enabled",
+                message
+            );
+            return None;
+        }
+        match message {
+            // Both sides asked at once, the side that sent DO keeps its
+            // request and the other side answers it.
+            Charset::Request(_)
+                if self.charset_requested
+                    && self.remote_enabled(TelnetOption::Charset) =>
+            {
+                self.send_argument(TelnetOption::Charset, &Charset::Rejected);
+                None
+            }
+            Charset::Request(names) => {
+                self.charset_requested = false;
+                let accepted = names.into_iter().find_map(|name| {
+                    Encoding::from_name(&name)
+                        .filter(|charset| {
+                            self.options.charsets().contains(charset)
+                        })
+                        .map(|charset| (name, charset))
+                });
+                match accepted {
+                    Some((name, charset)) => {
+                        let reply = Charset::Accepted(name);
+                        self.send_argument(TelnetOption::Charset, &reply);
+                        self.agree_charset(charset)
+                    }
+                    None => {
+                        let reply = Charset::Rejected;
+                        self.send_argument(TelnetOption::Charset, &reply);
+                        None
+                    }
+                }
+            }
+            Charset::Accepted(name) if self.charset_requested => {
+                self.charset_requested = false;
+                match Encoding::from_name(&name)
+                    .filter(|charset| self.options.charsets().contains(charset))
+                {
+                    Some(charset) => self.agree_charset(charset),
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Remote accepted {} which was never offered",
+                            name
+                        );
+                        None
+                    }
+                }
+            }
+            Charset::Rejected if self.charset_requested => {
+                self.charset_requested = false;
+                event!(Level::DEBUG, "Remote rejected every offered charset");
+                None
+            }
+            Charset::TTableIs(_) => {
+                let reply = Charset::TTableRejected;
+                self.send_argument(TelnetOption::Charset, &reply);
+                None
+            }
+            message => {
+                event!(Level::DEBUG, "Ignoring unexpected {:?}", message);
+                None
+            }
+        }
+    }
+    ///
+    /// Exchange text in `charset` from now on, or fall back on the 8-bit
+    /// policy when `None`. Input received before the switch is delivered
+    /// in the previous character set.
+    ///
+    pub fn set_charset(&mut self, charset: Option<Encoding>) {
+        let binary = self.remote_enabled(TelnetOption::TransmitBinary);
+        self.flush_input(binary, false);
+        self.charset = charset;
+    }
+    ///
+    /// Switch text to a `charset` agreed with the remote.
+    ///
+    fn agree_charset(&mut self, charset: Encoding) -> Option<TerminalOutput> {
+        self.set_charset(Some(charset));
+        Some(TerminalOutput::Charset(charset))
+    }
+    ///
+    /// LINEMODE editing mode agreed with the remote, if LINEMODE is enabled.
+    ///
+    pub fn linemode(&self) -> Option<LinemodeMode> {
+        let enabled = self.local_enabled(TelnetOption::Linemode)
+            || self.remote_enabled(TelnetOption::Linemode);
+        if enabled {
+            Some(self.linemode)
+        } else {
+            None
+        }
+    }
+    ///
+    /// Propose a new LINEMODE editing mode. The mode takes effect once the
+    /// remote acknowledges it. Returns `false` if LINEMODE is not enabled.
+    ///
+    pub fn set_linemode(&mut self, mode: LinemodeMode) -> bool {
+        if self.linemode().is_none() {
+            return false;
+        }
+        self.propose_linemode(mode);
+        true
+    }
+    ///
+    /// Send `mode` to the remote, remembering it until it is acknowledged.
+    ///
+    fn propose_linemode(&mut self, mode: LinemodeMode) {
+        self.linemode_proposed = Some(mode);
+        let message = Linemode::Mode { mode, ack: false };
+        self.send_argument(TelnetOption::Linemode, &message);
+    }
+    ///
+    /// LINEMODE special character table as currently agreed.
+    ///
+    pub fn special_characters(&self) -> &SlcTable {
+        &self.special_characters
+    }
+    ///
+    /// Characters the server asked to have input forwarded on, as a bit
+    /// mask indexed by character.
+    ///
+    pub fn forward_mask(&self) -> Option<&[u8]> {
+        self.forward_mask.as_deref()
+    }
+    ///
+    /// Ask the client to forward its input whenever a character whose bit is
+    /// set in `mask` is typed, or to stop using a mask when `None`. Returns
+    /// `false` unless the remote has enabled LINEMODE.
+    ///
+    pub fn set_forward_mask(&mut self, mask: Option<Vec<u8>>) -> bool {
+        if !self.remote_enabled(TelnetOption::Linemode) {
+            return false;
+        }
+        let message = match mask {
+            Some(mask) => ForwardMask::Do(mask),
+            None => ForwardMask::Dont,
+        };
+        self.send_argument(
+            TelnetOption::Linemode,
+            &Linemode::ForwardMask(message),
+        );
+        true
+    }
+    ///
+    /// Handle a LINEMODE subnegotiation.
+    ///
+    fn receive_linemode(
+        &mut self,
+        message: Linemode,
+    ) -> Option<TerminalOutput> {
+        if self.linemode().is_none() {
+            event!(
+                Level::DEBUG,
+                "Ignoring {:?}, LINEMODE is not enabled",
+                message
+            );
+            return None;
+        }
+        match message {
+            // Only the mode this side proposed may be acknowledged.
+            Linemode::Mode { mode, ack: true }
+                if self.linemode_proposed != Some(mode) =>
+            {
+                event!(
+                    Level::DEBUG,
+                    "Ignoring unrequested MODE ACK {:?}",
+                    mode
+                );
+                None
+            }
+            Linemode::Mode { mode, ack: true } => {
+                self.linemode_proposed = None;
+                if mode == self.linemode {
+                    return None;
+                }
+                self.linemode = mode;
+                Some(TerminalOutput::Linemode(mode))
+            }
+            Linemode::Mode { mode, .. } if mode == self.linemode => None,
+            Linemode::Mode { mode, .. } => {
+                let reply = Linemode::Mode { mode, ack: true };
+                self.send_argument(TelnetOption::Linemode, &reply);
+                self.linemode_proposed = None;
+                self.linemode = mode;
+                Some(TerminalOutput::Linemode(mode))
+            }
+            Linemode::ForwardMask(ForwardMask::Do(mask))
+                if self.local_enabled(TelnetOption::Linemode) =>
+            {
+                let reply = Linemode::ForwardMask(ForwardMask::Will);
+                self.send_argument(TelnetOption::Linemode, &reply);
+                self.forward_mask = Some(mask.clone());
+                Some(TerminalOutput::ForwardMask(Some(mask)))
+            }
+            Linemode::ForwardMask(ForwardMask::Dont)
+                if self.local_enabled(TelnetOption::Linemode) =>
+            {
+                let reply = Linemode::ForwardMask(ForwardMask::Wont);
+                self.send_argument(TelnetOption::Linemode, &reply);
+                self.forward_mask.take()?;
+                Some(TerminalOutput::ForwardMask(None))
+            }
+            Linemode::ForwardMask(message) => {
+                event!(
+                    Level::DEBUG,
+                    "Remote answered FORWARDMASK {:?}",
+                    message
+                );
+                None
+            }
+            Linemode::Slc(triplets) => {
+                self.receive_special_characters(triplets)
+            }
+        }
+    }
+    ///
+    /// Merge special characters proposed by the remote into the table,
+    /// acknowledging the ones adopted and answering the others with the
+    /// local value.
+    ///
+    fn receive_special_characters(
+        &mut self,
+        triplets: Vec<SlcTriplet>,
+    ) -> Option<TerminalOutput> {
+        let mut replies = Vec::new();
+        let mut changed = Vec::new();
+        for triplet in triplets {
+            if triplet.function == SlcFunction::Unknown(0) {
+                // Function zero asks for the whole table, reset to defaults
+                // first for SLC_DEFAULT.
+                if triplet.level == SlcLevel::Default {
+                    self.special_characters = default_slc_table();
+                }
+                replies.extend(self.special_characters.values().copied());
+                continue;
+            }
+            let current = match self.special_characters.get(&triplet.function) {
+                Some(current) => *current,
+                None => {
+                    // Never heard of it, so it cannot be supported.
+                    replies.push(SlcTriplet::new(
+                        triplet.function,
+                        SlcLevel::NoSupport,
+                        0,
+                    ));
+                    continue;
+                }
+            };
+            if triplet.ack || triplet.level == SlcLevel::CantChange {
+                if triplet.level == SlcLevel::CantChange
+                    && !triplet.ack
+                    && !current.same_mapping(&triplet)
+                {
+                    // The remote cannot move, so adopt its value unasked.
+                    let mut reply = triplet;
+                    reply.ack = true;
+                    replies.push(reply);
+                }
+                if !current.same_mapping(&triplet) {
+                    let mut adopted = triplet;
+                    adopted.ack = false;
+                    self.special_characters.insert(adopted.function, adopted);
+                    changed.push(adopted);
+                }
+                continue;
+            }
+            match triplet.level {
+                SlcLevel::Default => {
+                    let default = default_slc_table()[&triplet.function];
+                    if !current.same_mapping(&default) {
+                        self.special_characters
+                            .insert(default.function, default);
+                        changed.push(default);
+                    }
+                    replies.push(default);
+                }
+                _ if current.same_mapping(&triplet) => {}
+                _ if current.level == SlcLevel::CantChange => {
+                    replies.push(current);
+                }
+                _ => {
+                    let mut reply = triplet;
+                    reply.ack = true;
+                    replies.push(reply);
+                    self.special_characters.insert(triplet.function, triplet);
+                    changed.push(triplet);
+                }
+            }
+        }
+        if !replies.is_empty() {
+            self.send_argument(TelnetOption::Linemode, &Linemode::Slc(replies));
+        }
+        if changed.is_empty() {
+            None
+        } else {
+            Some(TerminalOutput::SpecialCharacters(changed))
+        }
+    }
+    ///
+    /// Options currently enabled on each side, as reported through STATUS.
+    ///
+    pub fn status(&self) -> TelnetOptionStatus {
+        let mut status = TelnetOptionStatus::new();
+        for code in 0..=u8::MAX {
+            let option = TelnetOption::from(code);
+            status.set(
+                option,
+                self.local_enabled(option),
+                self.remote_enabled(option),
+            );
+        }
+        status
+    }
+    ///
+    /// Ask the remote to report the options it considers enabled, which
+    /// arrives as `TerminalOutput::Status`. Returns `false` unless the
+    /// remote has enabled STATUS.
+    ///
+    pub fn request_status(&mut self) -> bool {
+        if !self.remote_enabled(TelnetOption::Status) {
+            return false;
+        }
+        self.send_argument(TelnetOption::Status, &Status::Send);
+        true
+    }
+    ///
+    /// Handle a STATUS subnegotiation, answering requests with the current
+    /// state of every option and logging where a report disagrees with it.
+    ///
+    fn receive_status(&mut self, message: Status) -> Option<TerminalOutput> {
+        match message {
+            Status::Send if self.local_enabled(TelnetOption::Status) => {
+                let reply = Status::Is(self.status());
+                self.send_argument(TelnetOption::Status, &reply);
+                None
+            }
+            Status::Is(report) if self.remote_enabled(TelnetOption::Status) => {
+                let status = self.status();
+                let expected = report.reversed();
+                let mut options = status.options();
+                options.extend(expected.options());
+                options.sort_by_key(|option| u8::from(*option));
+                options.dedup();
+                for option in options {
+                    let local = (status.local(option), expected.local(option));
+                    let remote =
+                        (status.remote(option), expected.remote(option));
+                    if local.0 != local.1 || remote.0 != remote.1 {
+                        event!(
+                            Level::WARN,
+                            "STATUS mismatch for {:?}: local {} remote {}, \
+                             remote reports local {} remote {}",
+                            option,
+                            local.0,
+                            remote.0,
+                            local.1,
+                            remote.1
+                        );
+                    }
+                }
+                Some(TerminalOutput::Status(report))
+            }
+            message => {
+                event!(Level::DEBUG, "Ignoring unexpected {:?}", message);
+                None
+            }
+        }
+    }
+    ///
+    /// How input from the remote is currently delivered.
+    ///
+    pub fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+    ///
+    /// Switch between line and character at a time input, negotiating ECHO
+    /// and SUPPRESS-GO-AHEAD on the local side to match. Resolves to `true`
+    /// once the remote agrees to both.
+    ///
+    /// While ECHO is enabled locally received data is echoed back.
+    ///
+    pub async fn set_input_mode(
+        &mut self,
+        mode: InputMode,
+    ) -> Result<bool, TerminalError> {
+        self.input_mode = mode;
+        if mode == InputMode::Character {
+            let binary = self.remote_enabled(TelnetOption::TransmitBinary);
+            self.deliver_keys(binary);
+        }
+        let character = mode == InputMode::Character;
+        let local = TerminalEndpoint::Local;
+        let echo = self.request(local, TelnetOption::Echo, character).await?;
+        let sga = self
+            .request(local, TelnetOption::SuppressGoAhead, character)
+            .await?;
+        Ok(echo && sga)
+    }
+    ///
+    /// Send `prompt` and read one line from the remote without echoing it,
+    /// returning the line without its newline.
+    ///
+    /// ECHO is enabled locally while reading so the remote stops echoing
+    /// too, and restored afterwards. The line is never returned from the
+    /// `Stream` and is kept out of tracing events.
+    ///
+    pub async fn read_secret(
+        &mut self,
+        prompt: &str,
+    ) -> Result<String, TerminalError> {
+        let echo = self.local_enabled(TelnetOption::Echo);
+        if !echo {
+            self.enable_local(TelnetOption::Echo).await?;
+        }
+        let prompt = self.encode_text(prompt);
+        self.replies.push_back(TelnetFrame::Data(prompt));
+        self.secret = Some(String::new());
+        self.framed.codec_mut().set_redacted(true);
+        let guard = SecretGuard(self);
+        let line = future::poll_fn(|cx| guard.0.poll_secret(cx)).await;
+        guard.0.secret = None;
+        drop(guard);
+        let line = line?;
+        event!(Level::DEBUG, "Read secret of {} bytes", line.len());
+        if self.local_enabled(TelnetOption::Echo) {
+            // The newline ending the secret was not echoed either.
+            let newline = self.encode_text("\n");
+            self.replies.push_back(TelnetFrame::Data(newline));
+        }
+        if !echo {
+            self.disable_local(TelnetOption::Echo).await?;
+        }
+        Ok(line)
+    }
+    ///
+    /// Drive the connection until a whole line of secret text has arrived.
+    ///
+    fn poll_secret(
+        &mut self,
         cx: &mut Context<'_>,
-    ) -> Poll<Option<Self::Item>> {
+    ) -> Poll<Result<String, TerminalError>> {
         loop {
-            let frame = self.framed.poll_next(cx);
+            if let Poll::Ready(Err(error)) = self.poll_transmit(cx) {
+                return Poll::Ready(Err(error.into()));
+            }
+            let secret = self.secret.as_mut().expect("reading a secret");
+            if let Some(end) = secret.find('\n') {
+                let rest = secret.split_off(end + 1);
+                let mut line = std::mem::take(secret);
+                line.pop();
+                // Binary input carries the CR of the newline through.
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+                // Anything typed after the secret is ordinary input.
+                if !rest.is_empty() {
+                    self.pending.push_back(TerminalOutput::AsciiData(rest));
+                }
+                return Poll::Ready(Ok(line));
+            }
+            let frame = match Pin::new(&mut self.framed).poll_next(cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => match self.idle.as_mut() {
+                    Some(idle) => {
+                        ready!(Pin::new(idle).poll(cx));
+                        let binary =
+                            self.remote_enabled(TelnetOption::TransmitBinary);
+                        self.flush_input(binary, false);
+                        continue;
+                    }
+                    None => return Poll::Pending,
+                },
+            };
             match frame {
-                TelnetFrame::Data(ch) => {
-                    // TODO: Handle Data Stream
-                    self.buffer.put_u8(ch);
-                    if self.buffer.len() >= self.buffer.capacity() {
-                        let data = Vec::from(self.buffer.as_ref());
-                        return Poll::Ready(Some(TerminalOutput::BinaryData(data)));
+                Some(Ok(frame)) => {
+                    if let Some(output) = self.receive(frame) {
+                        self.pending.push_back(output);
+                    }
+                }
+                Some(Err(error)) => return Poll::Ready(Err(error.into())),
+                None => return Poll::Ready(Err(TerminalError::Closed)),
+            }
+        }
+    }
+    ///
+    /// Send DO TIMING-MARK and wait for the remote to answer, returning the
+    /// round-trip time. The answer comes after the remote has processed
+    /// everything sent before the request.
+    ///
+    pub async fn timing_mark(&mut self) -> Result<Duration, TerminalError> {
+        self.replies
+            .push_back(TelnetFrame::Do(TelnetOption::TimingMark));
+        self.timing_marks_sent += 1;
+        let mark = self.timing_marks_sent;
+        self.timing_marks.push_back((mark, false));
+        let start = Instant::now();
+        let timeout = self.options.timeout();
+        // The mark is forgotten however this ends, so a mark the remote
+        // never answers cannot take the answer to a later one.
+        let guard = TimingMarkGuard(self, mark);
+        let answered = future::poll_fn(|cx| guard.0.poll_timing_mark(cx, mark));
+        match time::timeout(timeout, answered).await {
+            Ok(Ok(())) => Ok(start.elapsed()),
+            Ok(Err(error)) => Err(error),
+            Err(_) => Err(TerminalError::Timeout),
+        }
+    }
+    ///
+    /// Drive the connection until the remote has answered timing mark
+    /// number `mark`.
+    ///
+    fn poll_timing_mark(
+        &mut self,
+        cx: &mut Context<'_>,
+        mark: u64,
+    ) -> Poll<Result<(), TerminalError>> {
+        loop {
+            if let Poll::Ready(Err(error)) = self.poll_transmit(cx) {
+                return Poll::Ready(Err(error.into()));
+            }
+            if self.timing_marks.contains(&(mark, true)) {
+                return Poll::Ready(Ok(()));
+            }
+            match ready!(Pin::new(&mut self.framed).poll_next(cx)) {
+                Some(Ok(frame)) => {
+                    if let Some(output) = self.receive(frame) {
+                        self.pending.push_back(output);
+                    }
+                }
+                Some(Err(error)) => return Poll::Ready(Err(error.into())),
+                None => return Poll::Ready(Err(TerminalError::Closed)),
+            }
+        }
+    }
+    ///
+    /// Request a change to `option` and wait for the negotiation to settle.
+    ///
+    /// Anything else received while waiting is kept and returned from the
+    /// `Stream` afterwards.
+    ///
+    async fn request(
+        &mut self,
+        endpoint: TerminalEndpoint,
+        option: TelnetOption,
+        enable: bool,
+    ) -> Result<bool, TerminalError> {
+        let policy = match endpoint {
+            TerminalEndpoint::Local => self.options.local_policy(option),
+            TerminalEndpoint::Remote => self.options.remote_policy(option),
+        };
+        if enable && policy == TerminalOptionState::Unsupported {
+            return Err(TerminalError::ConfigurationError);
+        }
+        self.negotiate(endpoint, option, |o| match (endpoint, enable) {
+            (TerminalEndpoint::Local, true) => o.enable_local(option),
+            (TerminalEndpoint::Local, false) => o.disable_local(option),
+            (TerminalEndpoint::Remote, true) => o.enable_remote(option),
+            (TerminalEndpoint::Remote, false) => o.disable_remote(option),
+        });
+        let timeout = self.options.timeout();
+        let settled =
+            future::poll_fn(|cx| self.poll_settled(cx, endpoint, option));
+        match time::timeout(timeout, settled).await {
+            Ok(Ok(enabled)) => Ok(enabled == enable),
+            Ok(Err(error)) => Err(error),
+            Err(_) => Err(TerminalError::Timeout),
+        }
+    }
+    ///
+    /// Drive the connection until `option` is no longer being negotiated.
+    ///
+    fn poll_settled(
+        &mut self,
+        cx: &mut Context<'_>,
+        endpoint: TerminalEndpoint,
+        option: TelnetOption,
+    ) -> Poll<Result<bool, TerminalError>> {
+        loop {
+            if let Poll::Ready(Err(error)) = self.poll_transmit(cx) {
+                return Poll::Ready(Err(error.into()));
+            }
+            let state = match endpoint {
+                TerminalEndpoint::Local => self.options.local_state(option),
+                TerminalEndpoint::Remote => self.options.remote_state(option),
+            };
+            match state {
+                NegotiationState::Yes => return Poll::Ready(Ok(true)),
+                NegotiationState::No => return Poll::Ready(Ok(false)),
+                _ => {}
+            }
+            match ready!(Pin::new(&mut self.framed).poll_next(cx)) {
+                Some(Ok(frame)) => {
+                    if let Some(output) = self.receive(frame) {
+                        self.pending.push_back(output);
                     }
                 }
-                TelnetFrame::AbortOutput => {
-                    return Poll::Ready(Some(TerminalOutput::AbortOutput));
+                Some(Err(error)) => return Poll::Ready(Err(error.into())),
+                None => return Poll::Ready(Err(TerminalError::Closed)),
+            }
+        }
+    }
+    ///
+    /// Run a negotiation event through the option table, queueing any reply
+    /// and reporting any change in the option's status.
+    ///
+    fn negotiate<F>(
+        &mut self,
+        endpoint: TerminalEndpoint,
+        option: TelnetOption,
+        event: F,
+    ) where
+        F: FnOnce(&mut TerminalConfiguration) -> Option<TelnetFrame>,
+    {
+        let enabled = self.options.option_enabled(endpoint, option);
+        if let Some(reply) = event(&mut self.options) {
+            self.replies.push_back(reply);
+        }
+        if enabled != self.options.option_enabled(endpoint, option) {
+            if endpoint == TerminalEndpoint::Remote
+                && option == TelnetOption::TransmitBinary
+            {
+                // Input received so far belongs to the previous mode.
+                self.flush_input(enabled, true);
+                self.eight_bit = false;
+            }
+            self.pending.push_back(TerminalOutput::TerminalOptionStatus(
+                endpoint,
+                option,
+                OptionStatus::from(!enabled),
+            ));
+            match (endpoint, option) {
+                (TerminalEndpoint::Local, TelnetOption::NAWS) if !enabled => {
+                    if let Some(size) = self.local_window_size {
+                        self.send_window_size(size);
+                    }
+                }
+                (_, TelnetOption::Linemode) if enabled => {
+                    self.linemode = LinemodeMode::default();
+                    self.linemode_proposed = None;
+                    self.special_characters = default_slc_table();
+                    self.forward_mask = None;
                 }
-                TelnetFrame::AreYouThere => {
-                    return Poll::Ready(Some(TerminalOutput::AreYouThere));
+                (TerminalEndpoint::Local, TelnetOption::Linemode) => {
+                    let table = self.special_characters.values().copied();
+                    let message = Linemode::Slc(table.collect());
+                    self.send_argument(TelnetOption::Linemode, &message);
                 }
-                TelnetFrame::Break => {
-                    return Poll::Ready(Some(TerminalOutput::Break));
+                (TerminalEndpoint::Remote, TelnetOption::Linemode) => {
+                    let mode = self.options.linemode();
+                    self.propose_linemode(mode);
                 }
-                TelnetFrame::DataMark => {
-                    return Poll::Ready(Some(TerminalOutput::DataMark));
+                (TerminalEndpoint::Local, TelnetOption::TTYPE) => {
+                    self.terminal_type_index = 0;
                 }
-                TelnetFrame::EraseCharacter => {
-                    return Poll::Ready(Some(TerminalOutput::EraseCharacter));
+                (TerminalEndpoint::Remote, TelnetOption::TTYPE) => {
+                    self.terminal_types = None;
+                    if !enabled {
+                        self.terminal_types = Some(TerminalType::default());
+                        self.send_argument(TelnetOption::TTYPE, &TTYPE::Send);
+                    }
                 }
-                TelnetFrame::EraseLine => {
-                    return Poll::Ready(Some(TerminalOutput::EraseLine));
+                _ => {}
+            }
+            self.dispatch(option, |handler, context| {
+                if enabled {
+                    handler.disabled(endpoint, context)
+                } else {
+                    handler.enabled(endpoint, context)
                 }
-                TelnetFrame::GoAhead => {
-                    return Poll::Ready(Some(TerminalOutput::GoAhead));
+            });
+        }
+    }
+    ///
+    /// Queue every complete line in the input buffer as `AsciiData`.
+    ///
+    fn assemble_lines(&mut self) {
+        let compatibility = self.options.compatibility();
+        while let Some(text) = line::take_line(
+            &mut self.buffer,
+            self.charset,
+            compatibility.recv_while_normal,
+            compatibility.invalid_utf8,
+        ) {
+            self.emit_text(text);
+        }
+    }
+    ///
+    /// Queue everything in the input buffer, as `BinaryData` if it was
+    /// received in binary mode or as NVT ASCII otherwise.
+    ///
+    /// Unless this is the `last` of the input in the current mode, a held CR
+    /// or incomplete UTF-8 sequence stays buffered.
+    ///
+    fn flush_input(&mut self, binary: bool, last: bool) {
+        self.idle = None;
+        if binary {
+            if self.secret.is_some() {
+                self.take_binary_secret(last);
+            }
+            if !self.buffer.is_empty() {
+                let data = self.buffer.split().to_vec();
+                self.pending.push_back(TerminalOutput::BinaryData(data));
+            }
+            return;
+        }
+        if last {
+            self.newlines.flush(&mut self.buffer);
+        }
+        self.assemble_lines();
+        let compatibility = self.options.compatibility();
+        let text = line::take_text(
+            &mut self.buffer,
+            self.charset,
+            compatibility.recv_while_normal,
+            compatibility.invalid_utf8,
+            last,
+        );
+        if !text.is_empty() {
+            self.emit_text(text);
+        }
+    }
+    ///
+    /// Move a secret typed in binary mode out of the input buffer as text,
+    /// leaving whatever follows its newline to be delivered as binary data.
+    ///
+    fn take_binary_secret(&mut self, last: bool) {
+        let end = match self.buffer.iter().position(|&b| b == b'\n') {
+            Some(end) => end + 1,
+            None if last || self.buffer.len() >= BUFFER_SIZE => {
+                self.buffer.len()
+            }
+            None => return,
+        };
+        let mut secret = self.buffer.split_to(end);
+        let text = line::take_text(
+            &mut secret,
+            self.charset,
+            EightBitPolicy::Utf8,
+            self.options.compatibility().invalid_utf8,
+            true,
+        );
+        self.emit_text(text);
+    }
+    ///
+    /// Queue received `text` as `AsciiData`, unless a secret is being read.
+    ///
+    fn emit_text(&mut self, text: String) {
+        match self.secret.as_mut() {
+            Some(secret) => secret.push_str(&text),
+            None => self.pending.push_back(TerminalOutput::AsciiData(text)),
+        }
+    }
+    ///
+    /// Queue everything in the input buffer a character at a time.
+    ///
+    fn deliver_keys(&mut self, binary: bool) {
+        if binary {
+            self.flush_input(true, false);
+            return;
+        }
+        let compatibility = self.options.compatibility();
+        let text = line::take_text(
+            &mut self.buffer,
+            self.charset,
+            compatibility.recv_while_normal,
+            compatibility.invalid_utf8,
+            false,
+        );
+        for key in text.chars() {
+            self.emit_text(key.to_string());
+        }
+    }
+    ///
+    /// Queue `data` for the remote.
+    ///
+    fn transmit(&mut self, data: Bytes) -> Result<(), EncodeError> {
+        let data = self.encode_data(data);
+        Pin::new(&mut self.framed).start_send(TelnetFrame::Data(data))
+    }
+    ///
+    /// Prepare `text` for the remote in the agreed character set, falling
+    /// back on the 8-bit policy when none was agreed.
+    ///
+    fn encode_text(&self, text: &str) -> Bytes {
+        let charset = match self.charset {
+            Some(charset) => charset,
+            None => {
+                return self
+                    .encode_data(Bytes::copy_from_slice(text.as_bytes()))
+            }
+        };
+        let data = charset.encode(text);
+        if self.local_enabled(TelnetOption::TransmitBinary) {
+            return Bytes::from(data);
+        }
+        let mut encoded = BytesMut::new();
+        nvt::encode_newlines(&data, &mut encoded);
+        encoded.freeze()
+    }
+    ///
+    /// Prepare `data` for the remote, as NVT ASCII unless binary
+    /// transmission has been negotiated.
+    ///
+    fn encode_data(&self, data: Bytes) -> Bytes {
+        if self.local_enabled(TelnetOption::TransmitBinary) {
+            data
+        } else {
+            let mut encoded = BytesMut::new();
+            match self.options.compatibility().send_while_normal {
+                EightBitPolicy::Strip => {
+                    let ascii: Vec<u8> =
+                        data.iter().map(|&b| b & 0x7F).collect();
+                    nvt::encode_newlines(&ascii, &mut encoded);
                 }
-                TelnetFrame::InterruptProcess => {
-                    return Poll::Ready(Some(TerminalOutput::InterruptProcess));
+                EightBitPolicy::PassThrough | EightBitPolicy::Utf8 => {
+                    nvt::encode_newlines(&data, &mut encoded);
                 }
-                TelnetFrame::NoOperation => {
-                    return Poll::Ready(Some(TerminalOutput::NoOperation));
+            }
+            encoded.freeze()
+        }
+    }
+    ///
+    /// Invoke the handler registered for `option`, if any, and queue whatever
+    /// it sends or emits.
+    ///
+    fn dispatch<F>(&mut self, option: TelnetOption, callback: F)
+    where
+        F: FnOnce(&mut dyn OptionHandler, &mut HandlerContext),
+    {
+        if let Some(handler) = self.handlers.get_mut(&option) {
+            let mut context = HandlerContext::new(option);
+            callback(handler.as_mut(), &mut context);
+            self.replies.extend(context.frames);
+            self.pending.extend(context.outputs);
+        }
+    }
+    ///
+    /// Move queued negotiation replies into the codec.
+    ///
+    fn poll_replies(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), EncodeError>> {
+        while !self.replies.is_empty() {
+            ready!(Pin::new(&mut self.framed).poll_ready(cx))?;
+            if let Some(frame) = self.replies.pop_front() {
+                Pin::new(&mut self.framed).start_send(frame)?;
+            }
+        }
+        while !self.timing_mark_replies.is_empty() {
+            // Everything sent before the mark must reach the remote first.
+            ready!(Pin::new(&mut self.framed).poll_flush(cx))?;
+            if let Some(frame) = self.timing_mark_replies.pop_front() {
+                Pin::new(&mut self.framed).start_send(frame)?;
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+    ///
+    /// Move queued negotiation replies into the codec and flush them.
+    ///
+    fn poll_transmit(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), EncodeError>> {
+        ready!(self.poll_replies(cx))?;
+        Pin::new(&mut self.framed).poll_flush(cx)
+    }
+    ///
+    /// Process a single frame from the remote, returning any output it
+    /// produces.
+    ///
+    fn receive(&mut self, frame: TelnetFrame) -> Option<TerminalOutput> {
+        match frame {
+            TelnetFrame::Data(data) => {
+                if self.local_enabled(TelnetOption::Echo)
+                    && self.secret.is_none()
+                {
+                    self.replies.push_back(TelnetFrame::Data(data.clone()));
                 }
-                TelnetFrame::Do(opt) => {
-                    let option = TelnetOption::from(opt);
-                    if self.options.0.is_none() {
-                        self.options.0 = (option, Side::Local, )
+                let binary = self.remote_enabled(TelnetOption::TransmitBinary);
+                if binary {
+                    self.buffer.extend_from_slice(&data);
+                } else {
+                    if !self.eight_bit && data.iter().any(|&b| b & 0x80 != 0) {
+                        self.eight_bit = true;
+                        event!(
+                            Level::WARN,
+                            "Remote sent 8-bit data without negotiating \
+                             TRANSMIT-BINARY, handling it as {:?}",
+                            self.options.compatibility().recv_while_normal
+                        );
                     }
-                    self.options[opt]
+                    self.newlines.decode(&data, &mut self.buffer);
                 }
-                TelnetFrame::Dont(opt) => {
-                    let option = TelnetOption::from(opt);
+                match self.input_mode {
+                    InputMode::Character => self.deliver_keys(binary),
+                    InputMode::Line => {
+                        if !binary {
+                            self.assemble_lines();
+                        }
+                        if self.buffer.len() >= BUFFER_SIZE
+                            || (binary && self.secret.is_some())
+                        {
+                            self.flush_input(binary, false);
+                        }
+                    }
                 }
-                TelnetFrame::Will(opt) => {
-                    let option = TelnetOption::from(opt);
+                // Partial input is delivered once the remote goes quiet.
+                self.idle = if self.buffer.is_empty() {
+                    None
+                } else {
+                    Some(time::delay_for(self.options.line_timeout()))
+                };
+                None
+            }
+            TelnetFrame::AbortOutput => Some(TerminalOutput::AbortOutput),
+            TelnetFrame::AreYouThere => Some(TerminalOutput::AreYouThere),
+            TelnetFrame::Break => Some(TerminalOutput::Break),
+            TelnetFrame::DataMark => Some(TerminalOutput::DataMark),
+            TelnetFrame::EraseCharacter => Some(TerminalOutput::EraseCharacter),
+            TelnetFrame::EraseLine => Some(TerminalOutput::EraseLine),
+            TelnetFrame::GoAhead => {
+                let binary = self.remote_enabled(TelnetOption::TransmitBinary);
+                self.flush_input(binary, false);
+                Some(TerminalOutput::GoAhead)
+            }
+            TelnetFrame::InterruptProcess => {
+                Some(TerminalOutput::InterruptProcess)
+            }
+            TelnetFrame::NoOperation => Some(TerminalOutput::NoOperation),
+            TelnetFrame::EndOfFile => Some(TerminalOutput::EndOfFile),
+            TelnetFrame::Suspend => Some(TerminalOutput::Suspend),
+            TelnetFrame::Abort => Some(TerminalOutput::Abort),
+            TelnetFrame::EndOfRecord => {
+                let binary = self.remote_enabled(TelnetOption::TransmitBinary);
+                self.flush_input(binary, false);
+                Some(TerminalOutput::EndOfRecord)
+            }
+            // TIMING-MARK is never actually enabled, so it stays out of the
+            // option table and every request is answered.
+            TelnetFrame::Do(TelnetOption::TimingMark) => {
+                let reply =
+                    match self.options.local_policy(TelnetOption::TimingMark) {
+                        TerminalOptionState::Allowed
+                        | TerminalOptionState::RequestOnConnect => {
+                            TelnetFrame::Will(TelnetOption::TimingMark)
+                        }
+                        _ => TelnetFrame::Wont(TelnetOption::TimingMark),
+                    };
+                self.timing_mark_replies.push_back(reply);
+                None
+            }
+            TelnetFrame::Will(TelnetOption::TimingMark)
+            | TelnetFrame::Wont(TelnetOption::TimingMark) => {
+                match self
+                    .timing_marks
+                    .iter_mut()
+                    .find(|(_, answered)| !*answered)
+                {
+                    Some((_, answered)) => *answered = true,
+                    None => {
+                        event!(Level::DEBUG, "Ignoring unsolicited TIMING-MARK")
+                    }
+                }
+                None
+            }
+            TelnetFrame::Dont(TelnetOption::TimingMark) => None,
+            TelnetFrame::Do(option) => {
+                self.negotiate(TerminalEndpoint::Local, option, |o| {
+                    o.receive_do(option)
+                });
+                None
+            }
+            TelnetFrame::Dont(option) => {
+                self.negotiate(TerminalEndpoint::Local, option, |o| {
+                    o.receive_dont(option)
+                });
+                None
+            }
+            TelnetFrame::Will(option) => {
+                self.negotiate(TerminalEndpoint::Remote, option, |o| {
+                    o.receive_will(option)
+                });
+                None
+            }
+            TelnetFrame::Wont(option) => {
+                self.negotiate(TerminalEndpoint::Remote, option, |o| {
+                    o.receive_wont(option)
+                });
+                None
+            }
+            TelnetFrame::Subnegotiate(TelnetOption::NAWS, arguments)
+                if !self.remote_enabled(TelnetOption::NAWS) =>
+            {
+                event!(
+                    Level::DEBUG,
+                    "Ignoring {} byte NAWS subnegotiation, NAWS is not enabled",
+                    arguments.len()
+                );
+                None
+            }
+            TelnetFrame::Subnegotiate(TelnetOption::NAWS, mut arguments) => {
+                let length = arguments.len();
+                match NAWS::decode(&mut arguments) {
+                    Some(size) if self.window_size != Some(size) => {
+                        self.window_size = Some(size);
+                        Some(TerminalOutput::WindowSize {
+                            width: size.width,
+                            height: size.height,
+                        })
+                    }
+                    Some(_) => None,
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Ignoring malformed {} byte NAWS subnegotiation",
+                            length
+                        );
+                        None
+                    }
+                }
+            }
+            TelnetFrame::Subnegotiate(
+                TelnetOption::NewEnvironment,
+                mut arguments,
+            ) => {
+                let length = arguments.len();
+                match NewEnviron::decode(&mut arguments) {
+                    Some(message) => self.receive_environment(
+                        TelnetOption::NewEnvironment,
+                        message,
+                    ),
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Ignoring malformed {} byte NEW-ENVIRON subnegotiation",
+                            length
+                        );
+                        None
+                    }
+                }
+            }
+            TelnetFrame::Subnegotiate(
+                TelnetOption::Environment,
+                mut arguments,
+            ) => {
+                let length = arguments.len();
+                match OldEnviron::decode_preferring(
+                    &mut arguments,
+                    self.environ_swapped,
+                ) {
+                    Some(OldEnviron { message, swapped }) => {
+                        if swapped != self.environ_swapped {
+                            event!(
+                                Level::DEBUG,
+                                "Remote {} the ENVIRON VAR and VALUE codes",
+                                if swapped { "exchanges" } else { "restored" }
+                            );
+                            self.environ_swapped = swapped;
+                        }
+                        self.receive_environment(
+                            TelnetOption::Environment,
+                            message,
+                        )
+                    }
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Ignoring malformed {} byte ENVIRON subnegotiation",
+                            length
+                        );
+                        None
+                    }
+                }
+            }
+            TelnetFrame::Subnegotiate(TelnetOption::Charset, mut arguments) => {
+                let length = arguments.len();
+                match Charset::decode(&mut arguments) {
+                    Some(message) => self.receive_charset(message),
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Ignoring malformed {} byte CHARSET subnegotiation",
+                            length
+                        );
+                        None
+                    }
+                }
+            }
+            TelnetFrame::Subnegotiate(
+                TelnetOption::Linemode,
+                mut arguments,
+            ) => {
+                let length = arguments.len();
+                match Linemode::decode(&mut arguments) {
+                    Some(message) => self.receive_linemode(message),
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Ignoring malformed {} byte LINEMODE subnegotiation",
+                            length
+                        );
+                        None
+                    }
+                }
+            }
+            TelnetFrame::Subnegotiate(TelnetOption::Status, mut arguments) => {
+                let length = arguments.len();
+                match Status::decode(&mut arguments) {
+                    Some(message) => self.receive_status(message),
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Ignoring malformed {} byte STATUS subnegotiation",
+                            length
+                        );
+                        None
+                    }
+                }
+            }
+            TelnetFrame::Subnegotiate(TelnetOption::TTYPE, mut arguments) => {
+                let length = arguments.len();
+                match TTYPE::decode(&mut arguments) {
+                    Some(ttype) => self.receive_terminal_type(ttype),
+                    None => {
+                        event!(
+                            Level::WARN,
+                            "Ignoring malformed {} byte TTYPE subnegotiation",
+                            length
+                        );
+                        None
+                    }
+                }
+            }
+            TelnetFrame::Subnegotiate(option, arguments) => {
+                if self.handlers.contains_key(&option) {
+                    self.dispatch(option, |handler, context| {
+                        handler.subnegotiation(&arguments, context)
+                    });
+                } else {
+                    event!(
+                        Level::DEBUG,
+                        "Ignoring {} byte subnegotiation for {:?}",
+                        arguments.len(),
+                        option
+                    );
+                }
+                None
+            }
+            TelnetFrame::ProtocolViolation(_) if self.secret.is_some() => {
+                event!(Level::WARN, "Remote violated protocol: <redacted>");
+                None
+            }
+            TelnetFrame::ProtocolViolation(violation) => {
+                event!(
+                    Level::WARN,
+                    "Remote violated protocol: {:?}",
+                    violation
+                );
+                None
+            }
+        }
+    }
+}
+
+///
+/// Ends the reading of a secret when dropped, even if `read_secret` is
+/// cancelled before the secret arrives.
+///
+struct SecretGuard<'a, S>(&'a mut NetworkVirtualTerminal<S>)
+where
+    S: AsyncWrite + AsyncRead;
+
+impl<'a, S> Drop for SecretGuard<'a, S>
+where
+    S: AsyncWrite + AsyncRead,
+{
+    fn drop(&mut self) {
+        if self.0.secret.take().is_some() {
+            // Cancelled, partial input may hold part of the secret.
+            self.0.buffer.clear();
+        }
+        self.0.framed.codec_mut().set_redacted(false);
+    }
+}
+
+///
+/// Forgets a TIMING-MARK request when dropped, whether it was answered,
+/// timed out or was cancelled.
+///
+struct TimingMarkGuard<'a, S>(&'a mut NetworkVirtualTerminal<S>, u64)
+where
+    S: AsyncWrite + AsyncRead;
+
+impl<'a, S> Drop for TimingMarkGuard<'a, S>
+where
+    S: AsyncWrite + AsyncRead,
+{
+    fn drop(&mut self) {
+        let mark = self.1;
+        self.0.timing_marks.retain(|(sent, _)| *sent != mark);
+    }
+}
+
+impl<S> Stream for NetworkVirtualTerminal<S>
+where
+    S: AsyncWrite + AsyncRead + Unpin,
+{
+    type Item = Result<TerminalOutput, TerminalError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            // Replies are flushed opportunistically so negotiation completes
+            // even when the application only ever reads.
+            if let Poll::Ready(Err(error)) = this.poll_transmit(cx) {
+                return Poll::Ready(Some(Err(error.into())));
+            }
+            if let Some(output) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(output)));
+            }
+            let frame = match Pin::new(&mut this.framed).poll_next(cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => match this.idle.as_mut() {
+                    Some(idle) => {
+                        ready!(Pin::new(idle).poll(cx));
+                        let binary =
+                            this.remote_enabled(TelnetOption::TransmitBinary);
+                        this.flush_input(binary, false);
+                        continue;
+                    }
+                    None => return Poll::Pending,
+                },
+            };
+            match frame {
+                Some(Ok(frame)) => {
+                    // Queued so output produced while receiving goes first.
+                    if let Some(output) = this.receive(frame) {
+                        this.pending.push_back(output);
+                    }
                 }
-                TelnetFrame::Wont(opt) => {
-                    let option = TelnetOption::from(opt);
+                Some(Err(error)) => {
+                    return Poll::Ready(Some(Err(error.into())))
                 }
-                TelnetFrame::Subnegotiate(opt, arguments) => {
-                    let option = TelnetOption::from(opt);
+                None => {
+                    let binary =
+                        this.remote_enabled(TelnetOption::TransmitBinary);
+                    this.flush_input(binary, true);
+                    return Poll::Ready(this.pending.pop_front().map(Ok));
                 }
             }
         }
-        frame // TODO: Remove this
     }
 }
 
 impl<S> Sink<TerminalInput> for NetworkVirtualTerminal<S>
-    where
-        S: AsyncWrite,
+where
+    S: AsyncWrite + AsyncRead + Unpin,
 {
     type Error = EncodeError;
 
@@ -161,57 +1787,89 @@ impl<S> Sink<TerminalInput> for NetworkVirtualTerminal<S>
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
-        self.framed.poll_ready(cx)
+        let this = self.get_mut();
+        ready!(this.poll_replies(cx))?;
+        Pin::new(&mut this.framed).poll_ready(cx)
     }
 
     fn start_send(
         self: Pin<&mut Self>,
         item: TerminalInput,
     ) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let framed = Pin::new(&mut this.framed);
         match item {
             TerminalInput::NoOperation => {
-                self.framed.start_send(TelnetFrame::NoOperation)
-            },
+                framed.start_send(TelnetFrame::NoOperation)?;
+            }
             TerminalInput::DataMark => {
-                self.framed.start_send(TelnetFrame::DataMark)
-            },
+                framed.start_send(TelnetFrame::DataMark)?;
+            }
             TerminalInput::Break => {
-                self.framed.start_send(TelnetFrame::Break)
-            },
+                framed.start_send(TelnetFrame::Break)?;
+            }
             TerminalInput::InterruptProcess => {
-                self.framed.start_send(TelnetFrame::InterruptProcess),
+                framed.start_send(TelnetFrame::InterruptProcess)?;
             }
             TerminalInput::AbortOutput => {
-                self.framed.start_send(TelnetFrame::AbortOutput)
-            },
+                framed.start_send(TelnetFrame::AbortOutput)?;
+            }
             TerminalInput::AreYouThere => {
-                self.framed.start_send(TelnetFrame::AreYouThere)
-            },
+                framed.start_send(TelnetFrame::AreYouThere)?;
+            }
             TerminalInput::EraseCharacter => {
-                self.framed.start_send(TelnetFrame::EraseCharacter)
-            },
+                framed.start_send(TelnetFrame::EraseCharacter)?;
+            }
             TerminalInput::EraseLine => {
-                self.framed.start_send(TelnetFrame::EraseLine)
-            },
+                framed.start_send(TelnetFrame::EraseLine)?;
+            }
             TerminalInput::GoAhead => {
-                self.framed.start_send(TelnetFrame::GoAhead)
-            },
+                framed.start_send(TelnetFrame::GoAhead)?;
+            }
+            TerminalInput::EndOfFile => {
+                framed.start_send(TelnetFrame::EndOfFile)?;
+            }
+            TerminalInput::Suspend => {
+                framed.start_send(TelnetFrame::Suspend)?;
+            }
+            TerminalInput::Abort => {
+                framed.start_send(TelnetFrame::Abort)?;
+            }
+            TerminalInput::EndOfRecord => {
+                framed.start_send(TelnetFrame::EndOfRecord)?;
+            }
             TerminalInput::AsciiData(data) => {
-                /// TODO: Handle Compatability settings
-                for byte in data.as_bytes() {
-                    self.framed.start_send(TelnetFrame::Data(*byte))
-                }
+                let data = this.encode_text(&data);
+                Pin::new(&mut this.framed)
+                    .start_send(TelnetFrame::Data(data))?;
             }
             TerminalInput::BinaryData(data) => {
-                /// TODO: Handle Compatability settings
-                for byte in data.as_bytes() {
-                    self.framed.start_send(TelnetFrame::Data(*byte))
-                }
+                this.transmit(Bytes::from(data))?;
+            }
+            TerminalInput::EnableLocalOption(option) => {
+                this.negotiate(TerminalEndpoint::Local, option, |o| {
+                    o.enable_local(option)
+                });
+            }
+            TerminalInput::DisableLocalOption(option) => {
+                this.negotiate(TerminalEndpoint::Local, option, |o| {
+                    o.disable_local(option)
+                });
+            }
+            TerminalInput::EnableRemoteOption(option) => {
+                this.negotiate(TerminalEndpoint::Remote, option, |o| {
+                    o.enable_remote(option)
+                });
+            }
+            TerminalInput::DisableRemoteOption(option) => {
+                this.negotiate(TerminalEndpoint::Remote, option, |o| {
+                    o.disable_remote(option)
+                });
+            }
+            TerminalInput::Subnegotiation(option, arguments) => {
+                framed
+                    .start_send(TelnetFrame::Subnegotiate(option, arguments))?;
             }
-            TerminalInput::EnableLocalOption(TelnetOption) => {},
-            TerminalInput::DisableLocalOption(TelnetOption) => {},
-            TerminalInput::EnableRemoteOption(TelnetOption) => {},
-            TerminalInput::DisableRemoteOption(TelnetOption) => {},
         }
         Ok(())
     }
@@ -220,18 +1878,1238 @@ impl<S> Sink<TerminalInput> for NetworkVirtualTerminal<S>
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
-        self.framed.poll_flush(cx)
+        let this = self.get_mut();
+        ready!(this.poll_replies(cx))?;
+        Pin::new(&mut this.framed).poll_flush(cx)
     }
 
     fn poll_close(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
-        self.framed.poll_close(cx)
+        let this = self.get_mut();
+        ready!(this.poll_replies(cx))?;
+        Pin::new(&mut this.framed).poll_close(cx)
     }
 }
 
-pub struct Compatibility {
-    send_unicode_while_normal: bool,
-    recv_unicode_while_normal: bool,
+#[cfg(test)]
+mod tests {
+    use super::NetworkVirtualTerminal;
+    use crate::codec::Argument;
+    use crate::codec::TelnetOption;
+    use crate::consts;
+    use crate::terminal::option::linemode::{
+        default_slc_table, Linemode, LinemodeMode, SlcFunction, SlcLevel,
+        SlcTriplet,
+    };
+    use crate::terminal::option::new_environ::{
+        Environment, EnvironmentVariable,
+    };
+    use crate::terminal::option::status::TelnetOptionStatus;
+    use crate::terminal::option::TerminalOptionState;
+    use crate::terminal::{
+        Compatibility, EightBitPolicy, Encoding, HandlerContext, InputMode,
+        OptionHandler, OptionStatus, TerminalConfiguration,
+        TerminalConfigurationBuilder, TerminalEndpoint, TerminalError,
+        TerminalInput, TerminalOutput,
+    };
+    use futures::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio::time;
+    use tokio_test::io::{Builder, Mock};
+
+    fn subnegotiation(option: u8, arguments: &[u8]) -> Vec<u8> {
+        let mut frame = vec![consts::IAC, consts::SB, option];
+        frame.extend_from_slice(arguments);
+        frame.extend_from_slice(&[consts::IAC, consts::SE]);
+        frame
+    }
+
+    fn naws(arguments: &[u8]) -> Vec<u8> {
+        subnegotiation(consts::option::NAWS, arguments)
+    }
+
+    fn terminal_type_send() -> Vec<u8> {
+        subnegotiation(consts::option::TTYPE, &[1])
+    }
+
+    fn terminal_type_is(name: &str) -> Vec<u8> {
+        let mut arguments = vec![0];
+        arguments.extend_from_slice(name.as_bytes());
+        subnegotiation(consts::option::TTYPE, &arguments)
+    }
+
+    fn environ(arguments: &[u8]) -> Vec<u8> {
+        subnegotiation(consts::option::NEW_ENVIRONMENT, arguments)
+    }
+
+    fn old_environ(arguments: &[u8]) -> Vec<u8> {
+        subnegotiation(consts::option::OLD_ENVIRONMENT, arguments)
+    }
+
+    fn charset(arguments: &[u8]) -> Vec<u8> {
+        subnegotiation(consts::option::CHARSET, arguments)
+    }
+
+    fn linemode(arguments: &[u8]) -> Vec<u8> {
+        subnegotiation(consts::option::LINEMODE, arguments)
+    }
+
+    fn configured(
+        stream: Mock,
+        configuration: TerminalConfigurationBuilder,
+    ) -> NetworkVirtualTerminal<Mock> {
+        NetworkVirtualTerminal::with_configuration(
+            stream,
+            configuration.build(),
+        )
+    }
+
+    fn option_enabled(
+        endpoint: TerminalEndpoint,
+        option: TelnetOption,
+    ) -> TerminalOutput {
+        TerminalOutput::TerminalOptionStatus(
+            endpoint,
+            option,
+            OptionStatus::Enabled,
+        )
+    }
+
+    async fn collect(
+        terminal: &mut NetworkVirtualTerminal<Mock>,
+    ) -> Vec<TerminalOutput> {
+        let mut outputs = Vec::new();
+        while let Some(output) = terminal.next().await {
+            outputs.push(output.unwrap());
+        }
+        outputs
+    }
+
+    #[tokio::test]
+    async fn enable_remote_accepted() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::NAWS])
+            .read(b"hi")
+            .read(&[consts::IAC, consts::WILL, consts::option::NAWS])
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let enabled = terminal.enable_remote(TelnetOption::NAWS).await;
+        assert!(enabled.unwrap());
+        assert!(terminal.remote_enabled(TelnetOption::NAWS));
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            option_enabled(TerminalEndpoint::Remote, TelnetOption::NAWS)
+        );
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("hi".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn enable_local_refused() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::WILL, consts::option::ECHO])
+            .read(&[consts::IAC, consts::DONT, consts::option::ECHO])
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let enabled = terminal.enable_local(TelnetOption::Echo).await;
+        assert!(!enabled.unwrap());
+        assert!(terminal.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn enable_remote_timeout() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::NAWS])
+            .wait(Duration::from_secs(5))
+            .build();
+        let configuration =
+            TerminalConfiguration::builder().timeout(Duration::from_millis(20));
+        let mut terminal = configured(stream, configuration);
+        match terminal.enable_remote(TelnetOption::NAWS).await {
+            Err(TerminalError::Timeout) => {}
+            other => panic!("Expected Timeout, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn enable_remote_closed() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::NAWS])
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        match terminal.enable_remote(TelnetOption::NAWS).await {
+            Err(TerminalError::Closed) => {}
+            other => panic!("Expected Closed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_on_connect() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::TTYPE])
+            .read(&[consts::IAC, consts::WILL, consts::option::TTYPE])
+            .write(&terminal_type_send())
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .remote(TelnetOption::TTYPE, TerminalOptionState::RequestOnConnect);
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            option_enabled(TerminalEndpoint::Remote, TelnetOption::TTYPE)
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Channel(Vec<u8>);
+
+    struct Aardwolf;
+
+    impl OptionHandler for Aardwolf {
+        fn enabled(
+            &mut self,
+            endpoint: TerminalEndpoint,
+            context: &mut HandlerContext,
+        ) {
+            if endpoint == TerminalEndpoint::Remote {
+                context.send(vec![1, 1]);
+            }
+        }
+        fn subnegotiation(
+            &mut self,
+            arguments: &[u8],
+            context: &mut HandlerContext,
+        ) {
+            context.emit(Channel(arguments.to_vec()));
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_option_handler() {
+        let option = TelnetOption::Unknown(102);
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::WILL, 102])
+            .write(&[consts::IAC, consts::DO, 102])
+            .write(&[
+                consts::IAC,
+                consts::SB,
+                102,
+                1,
+                1,
+                consts::IAC,
+                consts::SE,
+            ])
+            .read(&[
+                consts::IAC,
+                consts::SB,
+                102,
+                100,
+                3,
+                consts::IAC,
+                consts::SE,
+            ])
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .remote(option, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        terminal.register(option, Aardwolf);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::TerminalOptionStatus(
+                TerminalEndpoint::Remote,
+                option,
+                OptionStatus::Enabled,
+            )
+        );
+        match terminal.next().await.unwrap().unwrap() {
+            TerminalOutput::OptionEvent(received, event) => {
+                assert_eq!(received, option);
+                assert_eq!(
+                    event.downcast_ref::<Channel>(),
+                    Some(&Channel(vec![100, 3]))
+                );
+            }
+            other => panic!("Expected OptionEvent, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn nvt_newlines() {
+        let stream = Builder::new()
+            .write(b"one\r\ntwo\r\0")
+            .read(b"three\r\nfour\r")
+            .read(b"\0five\n")
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        terminal
+            .send(TerminalInput::AsciiData("one\ntwo\r".to_string()))
+            .await
+            .unwrap();
+        for line in &["three\n", "four\n", "five\n"] {
+            assert_eq!(
+                terminal.next().await.unwrap().unwrap(),
+                TerminalOutput::AsciiData(line.to_string())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn nvt_newlines_binary() {
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::WILL, consts::option::BINARY])
+            .write(&[consts::IAC, consts::DO, consts::option::BINARY])
+            .read(&[consts::IAC, consts::DO, consts::option::BINARY])
+            .write(&[consts::IAC, consts::WILL, consts::option::BINARY])
+            .write(b"one\ntwo\r")
+            .read(b"three\r\0four\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .option(TelnetOption::TransmitBinary, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        for _ in 0..2 {
+            terminal.next().await.unwrap().unwrap();
+        }
+        terminal
+            .send(TerminalInput::AsciiData("one\ntwo\r".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::BinaryData(b"three\r\0four\r\n".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn binary_mode_switch() {
+        let stream = Builder::new()
+            .write(b"caf\x69")
+            .read(b"caf\xE9\r")
+            .read(&[consts::IAC, consts::WILL, consts::option::BINARY])
+            .write(&[consts::IAC, consts::DO, consts::option::BINARY])
+            .read(b"caf\xE9\r")
+            .read(&[consts::IAC, consts::WONT, consts::option::BINARY])
+            .write(&[consts::IAC, consts::DONT, consts::option::BINARY])
+            .read(b"\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .remote(TelnetOption::TransmitBinary, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        terminal
+            .send(TerminalInput::BinaryData(b"caf\xE9".to_vec()))
+            .await
+            .unwrap();
+        let outputs = collect(&mut terminal).await;
+        assert_eq!(
+            outputs,
+            vec![
+                TerminalOutput::AsciiData("caf\x69\r".to_string()),
+                option_enabled(
+                    TerminalEndpoint::Remote,
+                    TelnetOption::TransmitBinary
+                ),
+                TerminalOutput::BinaryData(b"caf\xE9\r".to_vec()),
+                TerminalOutput::TerminalOptionStatus(
+                    TerminalEndpoint::Remote,
+                    TelnetOption::TransmitBinary,
+                    OptionStatus::Disabled,
+                ),
+                TerminalOutput::AsciiData("\n".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn eight_bit_compatibility() {
+        let stream = Builder::new()
+            .write("caf\u{e9}\r\n".as_bytes())
+            .read("caf\u{e9}\r\n".as_bytes())
+            .build();
+        let configuration =
+            TerminalConfiguration::builder().compatibility(Compatibility {
+                send_while_normal: EightBitPolicy::PassThrough,
+                recv_while_normal: EightBitPolicy::Utf8,
+                ..Compatibility::default()
+            });
+        let mut terminal = configured(stream, configuration);
+        terminal
+            .send(TerminalInput::AsciiData("caf\u{e9}\n".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("caf\u{e9}\n".to_string())
+        );
+
+        let stream = Builder::new().read(b"caf\xE9").build();
+        let configuration =
+            TerminalConfiguration::builder().compatibility(Compatibility {
+                send_while_normal: EightBitPolicy::Strip,
+                recv_while_normal: EightBitPolicy::PassThrough,
+                ..Compatibility::default()
+            });
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("caf\u{e9}".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn line_assembly() {
+        let stream = Builder::new()
+            .read(b"Name: ")
+            .read(&[consts::IAC, consts::GA])
+            .read(b"caf\xC3")
+            .read(b"\xA9\r\nhalf")
+            .wait(Duration::from_millis(50))
+            .read(b" line\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .compatibility(Compatibility {
+                recv_while_normal: EightBitPolicy::Utf8,
+                ..Compatibility::default()
+            })
+            .line_timeout(Duration::from_millis(10));
+        let mut terminal = configured(stream, configuration);
+        let outputs = collect(&mut terminal).await;
+        assert_eq!(
+            outputs,
+            vec![
+                TerminalOutput::AsciiData("Name: ".to_string()),
+                TerminalOutput::GoAhead,
+                TerminalOutput::AsciiData("caf\u{e9}\n".to_string()),
+                TerminalOutput::AsciiData("half".to_string()),
+                TerminalOutput::AsciiData(" line\n".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn character_mode() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::WILL, consts::option::ECHO])
+            .read(&[consts::IAC, consts::DO, consts::option::ECHO])
+            .write(&[consts::IAC, consts::WILL, consts::option::SGA])
+            .read(&[consts::IAC, consts::DO, consts::option::SGA])
+            .read(b"ab")
+            .write(b"ab")
+            .write(&[consts::IAC, consts::WONT, consts::option::ECHO])
+            .read(&[consts::IAC, consts::DONT, consts::option::ECHO])
+            .write(&[consts::IAC, consts::WONT, consts::option::SGA])
+            .read(&[consts::IAC, consts::DONT, consts::option::SGA])
+            .read(b"cd\r\n")
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let accepted = terminal.set_input_mode(InputMode::Character).await;
+        assert!(accepted.unwrap());
+        assert_eq!(terminal.input_mode(), InputMode::Character);
+        let mut outputs = Vec::new();
+        for _ in 0..4 {
+            outputs.push(terminal.next().await.unwrap().unwrap());
+        }
+        let accepted = terminal.set_input_mode(InputMode::Line).await;
+        assert!(accepted.unwrap());
+        assert!(!terminal.local_enabled(TelnetOption::Echo));
+        while let Some(output) = terminal.next().await {
+            outputs.push(output.unwrap());
+        }
+        let status = |option, status| {
+            TerminalOutput::TerminalOptionStatus(
+                TerminalEndpoint::Local,
+                option,
+                status,
+            )
+        };
+        assert_eq!(
+            outputs,
+            vec![
+                status(TelnetOption::Echo, OptionStatus::Enabled),
+                status(TelnetOption::SuppressGoAhead, OptionStatus::Enabled),
+                TerminalOutput::AsciiData("a".to_string()),
+                TerminalOutput::AsciiData("b".to_string()),
+                status(TelnetOption::Echo, OptionStatus::Disabled),
+                status(TelnetOption::SuppressGoAhead, OptionStatus::Disabled),
+                TerminalOutput::AsciiData("cd\n".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn read_secret() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::WILL, consts::option::ECHO])
+            .read(&[consts::IAC, consts::DO, consts::option::ECHO])
+            .write(b"Password: ")
+            .read(b"hunter2\r\nlook\r\n")
+            .write(b"\r\n")
+            .write(&[consts::IAC, consts::WONT, consts::option::ECHO])
+            .read(&[consts::IAC, consts::DONT, consts::option::ECHO])
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let secret = terminal.read_secret("Password: ").await.unwrap();
+        assert_eq!(secret, "hunter2");
+        assert!(!terminal.local_enabled(TelnetOption::Echo));
+        let outputs = collect(&mut terminal).await;
+        let status = |status| {
+            TerminalOutput::TerminalOptionStatus(
+                TerminalEndpoint::Local,
+                TelnetOption::Echo,
+                status,
+            )
+        };
+        assert_eq!(
+            outputs,
+            vec![
+                status(OptionStatus::Enabled),
+                TerminalOutput::AsciiData("look\n".to_string()),
+                status(OptionStatus::Disabled),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn read_secret_cancelled() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::WILL, consts::option::ECHO])
+            .read(&[consts::IAC, consts::DO, consts::option::ECHO])
+            .write(b"Password: ")
+            .read(b"hun")
+            .wait(Duration::from_millis(50))
+            .read(b"ok\r\n")
+            .write(b"ok\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .line_timeout(Duration::from_millis(5));
+        let mut terminal = configured(stream, configuration);
+        let secret = terminal.read_secret("Password: ");
+        assert!(time::timeout(Duration::from_millis(20), secret)
+            .await
+            .is_err());
+        let outputs = collect(&mut terminal).await;
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(TerminalEndpoint::Local, TelnetOption::Echo),
+                TerminalOutput::AsciiData("ok\n".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn read_secret_binary() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::WILL, consts::option::ECHO])
+            .read(&[consts::IAC, consts::WILL, consts::option::BINARY])
+            .write(&[consts::IAC, consts::DO, consts::option::BINARY])
+            .read(&[consts::IAC, consts::DO, consts::option::ECHO])
+            .write(b"Password: ")
+            .read(b"h\xC3\xA9llo\r\nlook\r\n")
+            .write(b"\r\n")
+            .write(&[consts::IAC, consts::WONT, consts::option::ECHO])
+            .read(&[consts::IAC, consts::DONT, consts::option::ECHO])
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .remote(TelnetOption::TransmitBinary, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        let secret = terminal.read_secret("Password: ").await.unwrap();
+        assert_eq!(secret, "h\u{e9}llo");
+        let outputs = collect(&mut terminal).await;
+        let echo = |status| {
+            TerminalOutput::TerminalOptionStatus(
+                TerminalEndpoint::Local,
+                TelnetOption::Echo,
+                status,
+            )
+        };
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(
+                    TerminalEndpoint::Remote,
+                    TelnetOption::TransmitBinary
+                ),
+                echo(OptionStatus::Enabled),
+                TerminalOutput::BinaryData(b"look\r\n".to_vec()),
+                echo(OptionStatus::Disabled),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn window_size_received() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::NAWS])
+            .read(&[consts::IAC, consts::WILL, consts::option::NAWS])
+            .read(&naws(&[0, 80, 0, 24]))
+            .read(&naws(&[0, 80, 0, 24]))
+            .read(&naws(&[0, consts::IAC, consts::IAC, 0, 24]))
+            .read(&[consts::IAC, consts::SB, consts::option::NAWS, 0])
+            .read(&[consts::IAC, consts::SE])
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        assert_eq!(terminal.window_size(), None);
+        let enabled = terminal.enable_remote(TelnetOption::NAWS).await;
+        assert!(enabled.unwrap());
+        let outputs = collect(&mut terminal).await;
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(TerminalEndpoint::Remote, TelnetOption::NAWS),
+                TerminalOutput::WindowSize {
+                    width: 80,
+                    height: 24
+                },
+                TerminalOutput::WindowSize {
+                    width: 255,
+                    height: 24
+                },
+            ]
+        );
+        assert_eq!(terminal.window_size(), Some((255, 24)));
+    }
+
+    #[tokio::test]
+    async fn window_size_unrequested() {
+        let stream = Builder::new()
+            .read(&naws(&[0, 80, 0, 24]))
+            .read(b"ok\r\n")
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("ok\n".to_string())
+        );
+        assert_eq!(terminal.window_size(), None);
+    }
+
+    #[tokio::test]
+    async fn window_size_sent() {
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::NAWS])
+            .write(&[consts::IAC, consts::WILL, consts::option::NAWS])
+            .write(&[consts::IAC, consts::SB, consts::option::NAWS, 0, 80, 0])
+            .write(&[24, consts::IAC, consts::SE])
+            .write(&[consts::IAC, consts::SB, consts::option::NAWS, 0])
+            .write(&[consts::IAC, consts::IAC, 0, 40, consts::IAC, consts::SE])
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::NAWS, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        terminal.set_window_size(80, 24);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            option_enabled(TerminalEndpoint::Local, TelnetOption::NAWS)
+        );
+        // Unchanged sizes are not resent
+        terminal.set_window_size(80, 24);
+        terminal.set_window_size(255, 40);
+        terminal.flush().await.unwrap();
+        assert!(terminal.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn terminal_type_cycle() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::TTYPE])
+            .read(&[consts::IAC, consts::WILL, consts::option::TTYPE])
+            .write(&terminal_type_send())
+            .read(&terminal_type_is("MUDLET"))
+            .write(&terminal_type_send())
+            .read(&terminal_type_is("XTERM-256COLOR"))
+            .write(&terminal_type_send())
+            .read(&terminal_type_is("MTTS 2829"))
+            .write(&terminal_type_send())
+            .read(&terminal_type_is("MTTS 2829"))
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let enabled = terminal.enable_remote(TelnetOption::TTYPE).await;
+        assert!(enabled.unwrap());
+        let outputs = collect(&mut terminal).await;
+        let report = terminal.terminal_type().unwrap().clone();
+        assert_eq!(report.names, vec!["MUDLET", "XTERM-256COLOR", "MTTS 2829"]);
+        let mtts = report.mtts.unwrap();
+        assert!(mtts.ansi && mtts.utf8 && mtts.colors_256 && mtts.truecolor);
+        assert!(!mtts.vt100 && !mtts.screen_reader);
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(TerminalEndpoint::Remote, TelnetOption::TTYPE),
+                TerminalOutput::TerminalType(report),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn terminal_type_answered() {
+        let mut builder = Builder::new();
+        builder
+            .read(&[consts::IAC, consts::DO, consts::option::TTYPE])
+            .write(&[consts::IAC, consts::WILL, consts::option::TTYPE]);
+        for name in &["ANSI", "MTTS 9", "MTTS 9", "ANSI"] {
+            builder
+                .read(&terminal_type_send())
+                .write(&terminal_type_is(name));
+        }
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::TTYPE, TerminalOptionState::Allowed)
+            .terminal_types(vec!["ANSI", "MTTS 9"]);
+        let mut terminal = configured(builder.build(), configuration);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            option_enabled(TerminalEndpoint::Local, TelnetOption::TTYPE)
+        );
+        assert!(terminal.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn environment_requested() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::NEW_ENVIRONMENT])
+            .read(&[consts::IAC, consts::WILL, consts::option::NEW_ENVIRONMENT])
+            .write(&environ(b"\x01\x00CLIENT_NAME\x00CHARSET"))
+            .read(&environ(b"\x00\x00CHARSET\x01UTF-8\x00CLIENT_NAME\x01X"))
+            .read(&environ(b"\x02\x00CLIENT_NAME"))
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let variables = vec![
+            EnvironmentVariable::var("CLIENT_NAME"),
+            EnvironmentVariable::var("CHARSET"),
+        ];
+        assert!(!terminal.request_environment(variables.clone()));
+        let enabled =
+            terminal.enable_remote(TelnetOption::NewEnvironment).await;
+        assert!(enabled.unwrap());
+        assert!(terminal.request_environment(variables));
+        let outputs = collect(&mut terminal).await;
+        let charset = EnvironmentVariable::var("CHARSET");
+        let client_name = EnvironmentVariable::var("CLIENT_NAME");
+        let mut reply = Environment::new();
+        reply.insert(charset.clone(), Some("UTF-8".to_string()));
+        reply.insert(client_name.clone(), Some("X".to_string()));
+        let mut info = Environment::new();
+        info.insert(client_name.clone(), None);
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(
+                    TerminalEndpoint::Remote,
+                    TelnetOption::NewEnvironment
+                ),
+                TerminalOutput::Environment(reply),
+                TerminalOutput::EnvironmentInfo(info),
+            ]
+        );
+        assert_eq!(
+            terminal.environment().get(&charset),
+            Some(&Some("UTF-8".to_string()))
+        );
+        assert_eq!(terminal.environment().get(&client_name), Some(&None));
+    }
+
+    #[tokio::test]
+    async fn environment_allowlist() {
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::NEW_ENVIRONMENT])
+            .write(&[
+                consts::IAC,
+                consts::WILL,
+                consts::option::NEW_ENVIRONMENT,
+            ])
+            .read(&environ(b"\x01\x00USER\x00HOME"))
+            .write(&environ(b"\x00\x00HOME\x00USER\x01ann"))
+            .read(&environ(b"\x01\x03"))
+            .write(&environ(b"\x00\x03ROOM\x01hall"))
+            .read(b"ok\r\n")
+            .write(&environ(b"\x02\x03ROOM\x01attic"))
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::NewEnvironment, TerminalOptionState::Allowed)
+            .environment(EnvironmentVariable::var("USER"), "ann")
+            .environment(EnvironmentVariable::user("ROOM"), "hall");
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            option_enabled(
+                TerminalEndpoint::Local,
+                TelnetOption::NewEnvironment
+            )
+        );
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("ok\n".to_string())
+        );
+        let room = EnvironmentVariable::user("ROOM");
+        terminal.set_environment(room.clone(), Some("hall".to_string()));
+        terminal.set_environment(room, Some("attic".to_string()));
+        terminal.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn legacy_environment_requested() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::OLD_ENVIRONMENT])
+            .read(&[consts::IAC, consts::WILL, consts::option::OLD_ENVIRONMENT])
+            .write(&old_environ(b"\x01\x00USER"))
+            .read(&old_environ(b"\x00\x01USER\x00ann"))
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let enabled = terminal.enable_remote(TelnetOption::Environment).await;
+        assert!(enabled.unwrap());
+        let user = EnvironmentVariable::var("USER");
+        assert!(terminal.request_environment(vec![user.clone()]));
+        let outputs = collect(&mut terminal).await;
+        let mut reply = Environment::new();
+        reply.insert(user, Some("ann".to_string()));
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(
+                    TerminalEndpoint::Remote,
+                    TelnetOption::Environment
+                ),
+                TerminalOutput::Environment(reply),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn legacy_environment_swapped() {
+        // The remote sends VAR with the RFC1408 VALUE code, so the reply
+        // uses the same convention.
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::OLD_ENVIRONMENT])
+            .write(&[
+                consts::IAC,
+                consts::WILL,
+                consts::option::OLD_ENVIRONMENT,
+            ])
+            .read(&old_environ(b"\x01\x01USER"))
+            .write(&old_environ(b"\x00\x01USER\x00ann"))
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::Environment, TerminalOptionState::Allowed)
+            .environment(EnvironmentVariable::var("USER"), "ann");
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            option_enabled(TerminalEndpoint::Local, TelnetOption::Environment)
+        );
+        assert!(terminal.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn charset_requested() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::CHARSET])
+            .read(&[consts::IAC, consts::WILL, consts::option::CHARSET])
+            .write(&charset(b"\x01 UTF-8 ISO-8859-1"))
+            .read(&charset(b"\x02ISO-8859-1"))
+            .read(b"caf\xE9\r\n")
+            .write(b"\xE9t\xE9 ?\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .charsets(vec![Encoding::Utf8, Encoding::Latin1]);
+        let mut terminal = configured(stream, configuration);
+        assert!(!terminal.request_charset());
+        let enabled = terminal.enable_remote(TelnetOption::Charset).await;
+        assert!(enabled.unwrap());
+        assert!(terminal.request_charset());
+        let mut outputs = Vec::new();
+        for _ in 0..3 {
+            outputs.push(terminal.next().await.unwrap().unwrap());
+        }
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(TerminalEndpoint::Remote, TelnetOption::Charset),
+                TerminalOutput::Charset(Encoding::Latin1),
+                TerminalOutput::AsciiData("caf\u{e9}\n".to_string()),
+            ]
+        );
+        assert_eq!(terminal.charset(), Some(Encoding::Latin1));
+        terminal
+            .send(TerminalInput::AsciiData("\u{e9}t\u{e9} \u{2603}\n".into()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn charset_answered() {
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::CHARSET])
+            .write(&[consts::IAC, consts::WILL, consts::option::CHARSET])
+            .read(&charset(b"\x01;KOI8-R;latin1;UTF-8"))
+            .write(&charset(b"\x02latin1"))
+            .read(&charset(b"\x04\x01table"))
+            .write(&charset(b"\x05"))
+            .read(&charset(b"\x01;KOI8-R"))
+            .write(&charset(b"\x03"))
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::Charset, TerminalOptionState::Allowed)
+            .charsets(vec![Encoding::Utf8, Encoding::Latin1]);
+        let mut terminal = configured(stream, configuration);
+        let outputs = collect(&mut terminal).await;
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(TerminalEndpoint::Local, TelnetOption::Charset),
+                TerminalOutput::Charset(Encoding::Latin1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn charset_unrequested() {
+        let stream = Builder::new()
+            .read(&charset(b"\x01;latin1"))
+            .read(b"ok\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::Charset, TerminalOptionState::Allowed)
+            .charsets(vec![Encoding::Latin1]);
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("ok\n".to_string())
+        );
+        assert_eq!(terminal.charset(), None);
+    }
+
+    #[tokio::test]
+    async fn static_code_page() {
+        let stream = Builder::new()
+            .read(b"\xC9\xCD\xBB\r\n")
+            .write(b"\xB0\xB1\xB2\r\n")
+            .read(b"\xF0\xD2\xC9\xD7\xC5\xD4\r\n")
+            .build();
+        let configuration =
+            TerminalConfiguration::builder().encoding(Encoding::Cp437);
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(terminal.charset(), Some(Encoding::Cp437));
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("\u{2554}\u{2550}\u{2557}\n".to_string())
+        );
+        terminal
+            .send(TerminalInput::AsciiData(
+                "\u{2591}\u{2592}\u{2593}\n".into(),
+            ))
+            .await
+            .unwrap();
+        terminal.set_charset(Some(Encoding::Koi8R));
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData(
+                "\u{041F}\u{0440}\u{0438}\u{0432}\u{0435}\u{0442}\n"
+                    .to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn linemode_server() {
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::WILL, consts::option::LINEMODE])
+            .write(&[consts::IAC, consts::DO, consts::option::LINEMODE])
+            .write(&linemode(&[1, 3]))
+            .read(&linemode(&[1, 7]))
+            // IP is unchanged, EC moves to ^H and BRK asks for our default
+            .read(&linemode(&[3, 3, 0x62, 0x03, 10, 2, 0x08, 2, 3, 0]))
+            .write(&linemode(&[3, 10, 0x82, 0x08, 2, 0, 0]))
+            .read(&[consts::IAC, consts::IP])
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .remote(TelnetOption::Linemode, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(terminal.linemode(), None);
+        let outputs = collect(&mut terminal).await;
+        let mode = LinemodeMode {
+            edit: true,
+            trapsig: true,
+            ..LinemodeMode::default()
+        };
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(
+                    TerminalEndpoint::Remote,
+                    TelnetOption::Linemode
+                ),
+                TerminalOutput::Linemode(mode),
+                TerminalOutput::SpecialCharacters(vec![SlcTriplet::new(
+                    SlcFunction::EraseCharacter,
+                    SlcLevel::Value,
+                    0x08
+                )]),
+                TerminalOutput::InterruptProcess,
+            ]
+        );
+        assert_eq!(terminal.linemode(), Some(mode));
+    }
+
+    #[tokio::test]
+    async fn linemode_client() {
+        let mut table = Vec::new();
+        Linemode::Slc(default_slc_table().values().copied().collect())
+            .encode(&mut table);
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::LINEMODE])
+            .write(&[consts::IAC, consts::WILL, consts::option::LINEMODE])
+            .write(&linemode(&table))
+            .read(&linemode(&[1, 3]))
+            .write(&linemode(&[1, 7]))
+            .read(&linemode(&[consts::DO, 2, 0x00, 0x24]))
+            .write(&linemode(&[consts::WILL, 2]))
+            // The server cannot change EC, so its value is adopted
+            .read(&linemode(&[3, 10, 1, 0x08]))
+            .write(&linemode(&[3, 10, 0x81, 0x08]))
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::Linemode, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        let outputs = collect(&mut terminal).await;
+        let mode = LinemodeMode::from_bits(3);
+        let erase = SlcTriplet::new(
+            SlcFunction::EraseCharacter,
+            SlcLevel::CantChange,
+            0x08,
+        );
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(TerminalEndpoint::Local, TelnetOption::Linemode),
+                TerminalOutput::Linemode(mode),
+                TerminalOutput::ForwardMask(Some(vec![0x00, 0x24])),
+                TerminalOutput::SpecialCharacters(vec![erase]),
+            ]
+        );
+        assert_eq!(terminal.linemode(), Some(mode));
+        assert_eq!(terminal.forward_mask(), Some(&[0x00, 0x24][..]));
+        assert_eq!(
+            terminal.special_characters()[&SlcFunction::EraseCharacter],
+            erase
+        );
+    }
+
+    #[tokio::test]
+    async fn status_answered() {
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::STATUS])
+            .write(&[consts::IAC, consts::WILL, consts::option::STATUS])
+            .read(&[consts::IAC, consts::WILL, consts::option::NAWS])
+            .write(&[consts::IAC, consts::DO, consts::option::NAWS])
+            .read(&[consts::IAC, consts::DO, consts::SE])
+            .write(&[consts::IAC, consts::WILL, consts::SE])
+            .read(&[consts::IAC, consts::DO, consts::IAC])
+            .write(&[consts::IAC, consts::WILL, consts::IAC])
+            .read(&subnegotiation(consts::option::STATUS, &[1]))
+            // SE is doubled by STATUS, IAC by the codec
+            .write(&subnegotiation(
+                consts::option::STATUS,
+                &[
+                    0,
+                    consts::WILL,
+                    consts::option::STATUS,
+                    consts::DO,
+                    consts::option::NAWS,
+                    consts::WILL,
+                    consts::SE,
+                    consts::SE,
+                    consts::WILL,
+                    consts::IAC,
+                    consts::IAC,
+                ],
+            ))
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::Status, TerminalOptionState::Allowed)
+            .remote(TelnetOption::NAWS, TerminalOptionState::Allowed)
+            .local(
+                TelnetOption::Unknown(consts::SE),
+                TerminalOptionState::Allowed,
+            )
+            .local(
+                TelnetOption::Unknown(consts::IAC),
+                TerminalOptionState::Allowed,
+            );
+        let mut terminal = configured(stream, configuration);
+        let mut outputs = 0;
+        while let Some(output) = terminal.next().await {
+            output.unwrap();
+            outputs += 1;
+        }
+        assert_eq!(outputs, 4);
+        assert!(terminal.status().local(TelnetOption::Status));
+        assert!(terminal.status().remote(TelnetOption::NAWS));
+    }
+
+    #[tokio::test]
+    async fn status_requested() {
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::WILL, consts::option::STATUS])
+            .write(&[consts::IAC, consts::DO, consts::option::STATUS])
+            .write(&subnegotiation(consts::option::STATUS, &[1]))
+            // The remote believes we agreed to echo
+            .read(&subnegotiation(
+                consts::option::STATUS,
+                &[0, consts::WILL, consts::option::STATUS, consts::DO, 1],
+            ))
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .remote(TelnetOption::Status, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        assert!(!terminal.request_status());
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            option_enabled(TerminalEndpoint::Remote, TelnetOption::Status)
+        );
+        assert!(terminal.request_status());
+        let mut report = TelnetOptionStatus::new();
+        report.set(TelnetOption::Status, true, false);
+        report.set(TelnetOption::Echo, false, true);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::Status(report.clone())
+        );
+        assert_ne!(terminal.status(), report.reversed());
+        report.set(TelnetOption::Echo, false, false);
+        assert_eq!(terminal.status(), report.reversed());
+    }
+
+    #[tokio::test]
+    async fn timing_mark_measured() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::TM])
+            .read(b"hi\r\n")
+            .wait(Duration::from_millis(20))
+            .read(&[consts::IAC, consts::WILL, consts::option::TM])
+            .write(&[consts::IAC, consts::DO, consts::option::TM])
+            .read(&[consts::IAC, consts::WONT, consts::option::TM])
+            // Unsolicited answers are not treated as negotiation
+            .read(&[consts::IAC, consts::WILL, consts::option::TM])
+            .read(b"ok\r\n")
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        let elapsed = terminal.timing_mark().await.unwrap();
+        assert!(elapsed >= Duration::from_millis(20));
+        terminal.timing_mark().await.unwrap();
+        let outputs = collect(&mut terminal).await;
+        assert_eq!(
+            outputs,
+            vec![
+                TerminalOutput::AsciiData("hi\n".to_string()),
+                TerminalOutput::AsciiData("ok\n".to_string()),
+            ]
+        );
+        assert!(!terminal.remote_enabled(TelnetOption::TimingMark));
+    }
+
+    #[tokio::test]
+    async fn timing_mark_unanswered() {
+        let stream = Builder::new()
+            .write(&[consts::IAC, consts::DO, consts::option::TM])
+            .write(&[consts::IAC, consts::DO, consts::option::TM])
+            .wait(Duration::from_millis(10))
+            .read(&[consts::IAC, consts::WILL, consts::option::TM])
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .timeout(Duration::from_millis(100));
+        let mut terminal = configured(stream, configuration);
+        match terminal.timing_mark().await {
+            Err(TerminalError::Timeout) => {}
+            other => panic!("Expected Timeout, got {:?}", other),
+        }
+        // The answer belongs to the second mark, not the forgotten first
+        let elapsed = terminal.timing_mark().await.unwrap();
+        assert!(elapsed >= Duration::from_millis(10));
+        assert!(elapsed < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn timing_mark_answered() {
+        let stream = Builder::new()
+            .write(b"prompt\r\n")
+            .read(&[consts::IAC, consts::DO, consts::option::TM])
+            .write(&[consts::IAC, consts::WILL, consts::option::TM])
+            .read(&[consts::IAC, consts::DO, consts::option::TM])
+            .write(&[consts::IAC, consts::WILL, consts::option::TM])
+            .read(b"ok\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::TimingMark, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        terminal
+            .send(TerminalInput::AsciiData("prompt\n".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("ok\n".to_string())
+        );
+        assert!(!terminal.local_enabled(TelnetOption::TimingMark));
+
+        // Without permission the mark is still answered, with WONT
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::TM])
+            .write(&[consts::IAC, consts::WONT, consts::option::TM])
+            .read(b"ok\r\n")
+            .build();
+        let mut terminal = NetworkVirtualTerminal::new(stream);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("ok\n".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn linemode_unrequested() {
+        let stream = Builder::new()
+            .read(&linemode(&[1, 3]))
+            .read(&linemode(&[3, 10, 2, 8]))
+            .read(b"ok\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .option(TelnetOption::Linemode, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            TerminalOutput::AsciiData("ok\n".to_string())
+        );
+        assert_eq!(terminal.linemode(), None);
+        assert_eq!(
+            terminal.special_characters()[&SlcFunction::EraseCharacter].value,
+            0x7F
+        );
+    }
+
+    #[tokio::test]
+    async fn linemode_client_ignores_ack() {
+        let mut table = Vec::new();
+        Linemode::Slc(default_slc_table().values().copied().collect())
+            .encode(&mut table);
+        let stream = Builder::new()
+            .read(&[consts::IAC, consts::DO, consts::option::LINEMODE])
+            .write(&[consts::IAC, consts::WILL, consts::option::LINEMODE])
+            .write(&linemode(&table))
+            // Acknowledges a mode the client never proposed
+            .read(&linemode(&[1, 7]))
+            .read(b"ok\r\n")
+            .build();
+        let configuration = TerminalConfiguration::builder()
+            .local(TelnetOption::Linemode, TerminalOptionState::Allowed);
+        let mut terminal = configured(stream, configuration);
+        let outputs = collect(&mut terminal).await;
+        assert_eq!(
+            outputs,
+            vec![
+                option_enabled(TerminalEndpoint::Local, TelnetOption::Linemode),
+                TerminalOutput::AsciiData("ok\n".to_string()),
+            ]
+        );
+        assert_eq!(terminal.linemode(), Some(LinemodeMode::default()));
+    }
 }
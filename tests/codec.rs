@@ -14,4 +14,319 @@
 // limitations under the License.
 //
 
-use tokio_telnet;
+use bytes::{Bytes, BytesMut};
+use tokio_telnet::codec::{
+    CodecConfiguration, DecodeError, DecoderMode, ProtocolViolation,
+    TelnetCodec, TelnetFrame, TelnetOption, ViolationPolicy,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn negotiation_round_trip() {
+    let frames = vec![
+        TelnetFrame::Do(TelnetOption::Echo),
+        TelnetFrame::Dont(TelnetOption::NAWS),
+        TelnetFrame::Will(TelnetOption::SuppressGoAhead),
+        TelnetFrame::Wont(TelnetOption::Unknown(102)),
+    ];
+    let mut codec = TelnetCodec::default();
+    let mut buffer = BytesMut::new();
+    for frame in frames.clone() {
+        codec.encode(frame, &mut buffer).unwrap();
+    }
+    let mut decoded = Vec::new();
+    while let Some(frame) = codec.decode(&mut buffer).unwrap() {
+        decoded.push(frame);
+    }
+    assert_eq!(frames, decoded);
+}
+
+#[test]
+fn command_round_trip() {
+    let frames = vec![
+        TelnetFrame::NoOperation,
+        TelnetFrame::DataMark,
+        TelnetFrame::Break,
+        TelnetFrame::InterruptProcess,
+        TelnetFrame::AbortOutput,
+        TelnetFrame::AreYouThere,
+        TelnetFrame::EraseCharacter,
+        TelnetFrame::EraseLine,
+        TelnetFrame::GoAhead,
+        TelnetFrame::EndOfFile,
+        TelnetFrame::Suspend,
+        TelnetFrame::Abort,
+        TelnetFrame::EndOfRecord,
+    ];
+    let mut codec = TelnetCodec::default();
+    let mut buffer = BytesMut::new();
+    for frame in frames.clone() {
+        codec.encode(frame, &mut buffer).unwrap();
+    }
+    let mut decoded = Vec::new();
+    while let Some(frame) = codec.decode(&mut buffer).unwrap() {
+        decoded.push(frame);
+    }
+    assert_eq!(frames, decoded);
+}
+
+///
+/// Deterministic xorshift generator so payload coverage is reproducible.
+///
+struct Payloads(u64);
+
+impl Iterator for Payloads {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let mut next = || {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        };
+        let len = (next() % 64) as usize;
+        // Bias towards IAC and SE so escaping is exercised heavily.
+        Some(
+            (0..len)
+                .map(|_| match next() % 4 {
+                    0 => 0xFF,
+                    1 => 0xF0,
+                    _ => next() as u8,
+                })
+                .collect(),
+        )
+    }
+}
+
+#[test]
+fn subnegotiation_round_trip() {
+    let mut codec = TelnetCodec::default();
+    let mut payloads: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![0xFF],
+        vec![0xFF, 0xFF],
+        vec![0xFF, 0xF0],
+        (0..=255).collect(),
+    ];
+    payloads.extend(Payloads(0x2545_F491_4F6C_DD1D).take(1000));
+    for payload in payloads {
+        let frame =
+            TelnetFrame::Subnegotiate(TelnetOption::GMCP, Bytes::from(payload));
+        let mut buffer = BytesMut::new();
+        codec.encode(frame.clone(), &mut buffer).unwrap();
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame));
+        assert!(buffer.is_empty());
+    }
+}
+
+#[test]
+fn subnegotiation_split_reads() {
+    let mut codec = TelnetCodec::default();
+    for payload in Payloads(0x9E37_79B9_7F4A_7C15).take(100) {
+        let frame =
+            TelnetFrame::Subnegotiate(TelnetOption::MSDP, Bytes::from(payload));
+        let mut encoded = BytesMut::new();
+        codec.encode(frame.clone(), &mut encoded).unwrap();
+        // Deliver the frame a byte at a time, as a slow link would.
+        let mut buffer = BytesMut::new();
+        let mut decoded = None;
+        for byte in encoded.iter() {
+            assert_eq!(decoded, None);
+            buffer.extend_from_slice(&[*byte]);
+            decoded = codec.decode(&mut buffer).unwrap();
+        }
+        assert_eq!(decoded, Some(frame));
+    }
+}
+
+#[test]
+fn subnegotiation_zero_copy() {
+    let mut codec = TelnetCodec::default();
+    let mut buffer = BytesMut::from(&b"\xFF\xFA\xC9Core.Hello {}\xFF\xF0"[..]);
+    let start = buffer.as_ptr() as usize;
+    match codec.decode(&mut buffer).unwrap() {
+        Some(TelnetFrame::Subnegotiate(TelnetOption::GMCP, arguments)) => {
+            assert_eq!(&arguments[..], b"Core.Hello {}");
+            // The payload is a view into the read buffer, not a copy.
+            assert_eq!(arguments.as_ptr() as usize, start + 3);
+        }
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+    assert!(buffer.is_empty());
+}
+
+/// Decode every frame in `input`, stopping at the first error.
+fn decode_all(
+    codec: &mut TelnetCodec,
+    input: &[u8],
+) -> Result<Vec<TelnetFrame>, DecodeError> {
+    let mut buffer = BytesMut::from(input);
+    let mut frames = Vec::new();
+    while let Some(frame) = codec.decode(&mut buffer)? {
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+#[test]
+fn subnegotiation_limit() {
+    // 8 byte payload, one escaped IAC, followed by data
+    let input = b"\xFF\xFA\xC9abc\xFF\xFFdefg\xFF\xF0ok";
+    let limited = |policy| {
+        TelnetCodec::with_configuration(
+            CodecConfiguration::builder()
+                .max_subnegotiation(4, policy)
+                .build(),
+        )
+    };
+
+    let mut codec = limited(ViolationPolicy::Drop);
+    assert_eq!(
+        decode_all(&mut codec, input).unwrap(),
+        vec![TelnetFrame::Data(Bytes::from_static(b"ok"))]
+    );
+
+    let mut codec = limited(ViolationPolicy::Truncate);
+    assert_eq!(
+        decode_all(&mut codec, input).unwrap(),
+        vec![
+            TelnetFrame::Subnegotiate(
+                TelnetOption::GMCP,
+                Bytes::from_static(b"abc\xFF")
+            ),
+            TelnetFrame::Data(Bytes::from_static(b"ok")),
+        ]
+    );
+
+    let mut codec = limited(ViolationPolicy::Error);
+    match decode_all(&mut codec, input) {
+        Err(DecodeError::SubnegotiationTooLong(4)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    // Payloads within the limit are untouched
+    let mut codec = limited(ViolationPolicy::Error);
+    assert_eq!(
+        decode_all(&mut codec, b"\xFF\xFA\xC9abcd\xFF\xF0").unwrap(),
+        vec![TelnetFrame::Subnegotiate(
+            TelnetOption::GMCP,
+            Bytes::from_static(b"abcd")
+        )]
+    );
+}
+
+#[test]
+fn pending_frames_limit() {
+    let input = b"\xFF\xF1\xFF\xF1\xFF\xF1\xFF\xF9";
+    let limited = |policy| {
+        TelnetCodec::with_configuration(
+            CodecConfiguration::builder()
+                .max_pending_frames(2, policy)
+                .build(),
+        )
+    };
+
+    let mut codec = limited(ViolationPolicy::Drop);
+    assert_eq!(
+        decode_all(&mut codec, input).unwrap(),
+        vec![TelnetFrame::NoOperation, TelnetFrame::NoOperation]
+    );
+    // The limit applies afresh once the decoder runs out of input
+    assert_eq!(
+        decode_all(&mut codec, b"\xFF\xF9").unwrap(),
+        vec![TelnetFrame::GoAhead]
+    );
+
+    let mut codec = limited(ViolationPolicy::Truncate);
+    let mut buffer = BytesMut::from(&input[..]);
+    assert_eq!(
+        codec.decode(&mut buffer).unwrap(),
+        Some(TelnetFrame::NoOperation)
+    );
+    assert_eq!(
+        codec.decode(&mut buffer).unwrap(),
+        Some(TelnetFrame::NoOperation)
+    );
+    assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+    assert!(buffer.is_empty());
+
+    let mut codec = limited(ViolationPolicy::Error);
+    match decode_all(&mut codec, input) {
+        Err(DecodeError::TooManyPendingFrames(2)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn unknown_command() {
+    let input = b"a\xFF\x01b";
+
+    let mut codec = TelnetCodec::default();
+    assert_eq!(
+        decode_all(&mut codec, input).unwrap(),
+        vec![
+            TelnetFrame::Data(Bytes::from_static(b"a")),
+            TelnetFrame::Data(Bytes::from_static(b"b")),
+        ]
+    );
+
+    let mut codec = TelnetCodec::with_configuration(
+        CodecConfiguration::builder().strict().build(),
+    );
+    match decode_all(&mut codec, input) {
+        Err(DecodeError::UnknownCommand(0x01)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn interrupted_subnegotiation() {
+    // Unescaped IAC inside the payload, as sent by many legacy servers
+    let input = b"\xFF\xFA\x18ab\xFFcd\xFF\xF0ok";
+    let with_mode = |mode| {
+        TelnetCodec::with_configuration(
+            CodecConfiguration::builder().mode(mode).build(),
+        )
+    };
+
+    let mut codec = with_mode(DecoderMode::Strict);
+    let mut buffer = BytesMut::from(&input[..]);
+    match codec.decode(&mut buffer) {
+        Err(DecodeError::InvalidSubnegotiation(b'c')) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    // The rest of the payload never surfaces as data
+    assert_eq!(
+        codec.decode(&mut buffer).unwrap(),
+        Some(TelnetFrame::Data(Bytes::from_static(b"ok")))
+    );
+
+    let mut codec = with_mode(DecoderMode::Lenient);
+    assert_eq!(
+        decode_all(&mut codec, input).unwrap(),
+        vec![
+            TelnetFrame::Subnegotiate(
+                TelnetOption::TTYPE,
+                Bytes::from_static(b"ab\xFFcd")
+            ),
+            TelnetFrame::Data(Bytes::from_static(b"ok")),
+        ]
+    );
+
+    let mut codec = with_mode(DecoderMode::Report);
+    assert_eq!(
+        decode_all(&mut codec, input).unwrap(),
+        vec![
+            TelnetFrame::ProtocolViolation(
+                ProtocolViolation::InterruptedSubnegotiation {
+                    option: TelnetOption::TTYPE,
+                    command: b'c',
+                    arguments: Bytes::from_static(b"ab"),
+                    discarded: 1,
+                }
+            ),
+            TelnetFrame::Data(Bytes::from_static(b"ok")),
+        ]
+    );
+}